@@ -1,3 +1,4 @@
 pub mod letter;
 pub mod word;
-pub mod puzzle;
\ No newline at end of file
+pub mod puzzle;
+pub mod symmetry;
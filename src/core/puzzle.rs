@@ -11,9 +11,12 @@ use crate::util::grid::Grid;
 use std::ops::{Index, IndexMut};
 use std::fmt::Display;
 use std::fmt;
-use crate::play::range_split::RangeSplitExt;
+use crate::util::range_split::RangeSplitExt;
 use std::cmp::Ordering;
 
+/// The shortest entry a puzzle may have.
+pub const MIN_LENGTH: usize = 3;
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Enum)]
 pub enum Direction {
     Across,
@@ -181,6 +184,23 @@ impl<T> WindowMap<T> {
     pub fn previous_window(&self, window: Window) -> Window {
         *self.windows.range(..window).nth_back(0).unwrap_or(self.windows.iter().last().unwrap()).0
     }
+    pub fn numbers(&self) -> Grid<Option<usize>> {
+        let mut next = 1;
+        Grid::new(self.grid.size(), |x, y| {
+            let starts = self.grid[(x, y)].values().any(|window| {
+                window.map_or(false, |window| window.position() == (x, y))
+            });
+            if starts {
+                next += 1;
+                Some(next - 1)
+            } else {
+                None
+            }
+        })
+    }
+    pub fn number(&self, window: Window) -> usize {
+        self.numbers()[window.position()].unwrap()
+    }
 
     /*pub fn verticals(&self) -> Vec<(usize, usize)> {
         iproduct!(0..self.grid.size().0,0..self.grid.size().1)
@@ -228,6 +248,57 @@ impl WindowMap<()> {
     }
 }
 
+/// The runs of white cells, across then down, that are too short to be entries.
+pub fn short_runs(white: &Grid<bool>) -> Vec<Vec<(usize, usize)>> {
+    let (width, height) = white.size();
+    let mut runs = vec![];
+    for y in 0..height {
+        for xs in (0..width).range_split(|&x| !white[(x, y)]) {
+            if xs.start < xs.end && xs.end - xs.start < MIN_LENGTH {
+                runs.push(xs.map(|x| (x, y)).collect());
+            }
+        }
+    }
+    for x in 0..width {
+        for ys in (0..height).range_split(|&y| !white[(x, y)]) {
+            if ys.start < ys.end && ys.end - ys.start < MIN_LENGTH {
+                runs.push(ys.map(|y| (x, y)).collect());
+            }
+        }
+    }
+    runs
+}
+
+/// The white cells split into groups connected through white neighbours.
+pub fn regions(white: &Grid<bool>) -> Vec<Vec<(usize, usize)>> {
+    let (width, height) = white.size();
+    let mut seen = Grid::new(white.size(), |_, _| false);
+    let mut regions = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            if !white[(x, y)] || seen[(x, y)] {
+                continue;
+            }
+            seen[(x, y)] = true;
+            let mut region = vec![(x, y)];
+            let mut next = 0;
+            while next < region.len() {
+                let (x, y) = region[next];
+                next += 1;
+                let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                for &(nx, ny) in neighbours.iter() {
+                    if nx < width && ny < height && white[(nx, ny)] && !seen[(nx, ny)] {
+                        seen[(nx, ny)] = true;
+                        region.push((nx, ny));
+                    }
+                }
+            }
+            regions.push(region);
+        }
+    }
+    regions
+}
+
 
 impl<T> Index<Window> for WindowMap<T> {
     type Output = T;
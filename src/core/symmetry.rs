@@ -0,0 +1,88 @@
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::util::grid::Grid;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Symmetry {
+    None,
+    /// Unchanged by a half turn about the centre.
+    Rotational,
+    /// Unchanged by swapping the left and right sides.
+    LeftRight,
+    /// Unchanged by swapping the top and bottom.
+    UpDown,
+    /// Unchanged by reflecting in the diagonal from the top left. Only square grids have one.
+    Diagonal,
+}
+
+pub const SYMMETRIES: [Symmetry; 5] = [Symmetry::Rotational, Symmetry::LeftRight, Symmetry::UpDown, Symmetry::Diagonal, Symmetry::None];
+
+impl Symmetry {
+    /// The cell that must match `(x, y)` in a grid of `size`, which may be the cell itself.
+    pub fn partner(self, (x, y): (usize, usize), (width, height): (usize, usize)) -> Option<(usize, usize)> {
+        match self {
+            Symmetry::None => None,
+            Symmetry::Rotational => Some((width - 1 - x, height - 1 - y)),
+            Symmetry::LeftRight => Some((width - 1 - x, y)),
+            Symmetry::UpDown => Some((x, height - 1 - y)),
+            Symmetry::Diagonal if width == height => Some((y, x)),
+            Symmetry::Diagonal => None,
+        }
+    }
+
+    /// The next mode in `SYMMETRIES`.
+    pub fn next(self) -> Self {
+        let index = SYMMETRIES.iter().position(|&symmetry| symmetry == self).unwrap();
+        SYMMETRIES[(index + 1) % SYMMETRIES.len()]
+    }
+
+    /// The white cells whose partner is black.
+    pub fn asymmetric_cells(self, white: &Grid<bool>) -> Vec<(usize, usize)> {
+        let (width, height) = white.size();
+        iproduct!(0..height, 0..width)
+            .map(|(y, x)| (x, y))
+            .filter(|&position| white[position])
+            .filter(|&position| self.partner(position, white.size()).map_or(false, |partner| !white[partner]))
+            .collect()
+    }
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Symmetry::None => "none",
+            Symmetry::Rotational => "rotational",
+            Symmetry::LeftRight => "left-right",
+            Symmetry::UpDown => "up-down",
+            Symmetry::Diagonal => "diagonal",
+        })
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        SYMMETRIES.iter().cloned()
+            .find(|symmetry| symmetry.to_string() == name)
+            .ok_or_else(|| format!("Unknown symmetry {}, expected one of {}",
+                                   name, SYMMETRIES.iter().map(|symmetry| symmetry.to_string()).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+#[test]
+fn test_symmetry() {
+    let white = Grid::new((3, 3), |x, y| (x, y) != (0, 0));
+    assert_eq!(Symmetry::Rotational.asymmetric_cells(&white), vec![(2, 2)]);
+    assert_eq!(Symmetry::LeftRight.asymmetric_cells(&white), vec![(2, 0)]);
+    assert_eq!(Symmetry::UpDown.asymmetric_cells(&white), vec![(0, 2)]);
+    assert_eq!(Symmetry::Diagonal.asymmetric_cells(&white), vec![]);
+    assert_eq!(Symmetry::None.asymmetric_cells(&white), vec![]);
+    assert_eq!(Symmetry::Diagonal.partner((1, 0), (3, 2)), None);
+    assert_eq!(Symmetry::Rotational.partner((1, 1), (3, 3)), Some((1, 1)));
+    for &symmetry in SYMMETRIES.iter() {
+        assert_eq!(symmetry.to_string().parse::<Symmetry>(), Ok(symmetry));
+    }
+    assert_eq!(Symmetry::None.next(), Symmetry::Rotational);
+}
@@ -2,7 +2,9 @@ use std::{fmt, io};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::ops::{Index, IndexMut, Deref};
+use std::ops::{Index, Deref};
+use std::cmp::Ordering;
+use std::sync::RwLock;
 
 use arrayvec::ArrayVec;
 
@@ -12,11 +14,45 @@ use crate::core::letter::Letter;
 use crate::util::bitset::{BitSet, IndexType};
 use std::iter::FromIterator;
 
+/// Words up to this length are stored inline. Longer ones keep their first `WIDTH`
+/// letters inline and are interned, so `Word` stays small and `Copy` whatever its length.
 pub const WIDTH: usize = 16;
 
-#[derive(Eq, Ord, PartialEq, PartialOrd, Hash, Copy, Clone)]
+#[derive(Default)]
+struct Interned {
+    ids: HashMap<&'static [Letter], u32>,
+    words: Vec<&'static [Letter]>,
+}
+
+lazy_static! {
+    static ref INTERNED: RwLock<Interned> = RwLock::new(Interned::default());
+}
+
+fn intern(letters: &[Letter]) -> u32 {
+    if let Some(&id) = INTERNED.read().unwrap().ids.get(letters) {
+        return id;
+    }
+    let mut interned = INTERNED.write().unwrap();
+    if let Some(&id) = interned.ids.get(letters) {
+        return id;
+    }
+    let leaked: &'static [Letter] = Box::leak(letters.to_vec().into_boxed_slice());
+    let id = interned.words.len() as u32;
+    interned.words.push(leaked);
+    interned.ids.insert(leaked, id);
+    id
+}
+
+fn interned(id: u32) -> &'static [Letter] {
+    INTERNED.read().unwrap().words[id as usize]
+}
+
+// The buffer is padded with `Letter::none()` and `id` is only set for words longer than
+// `WIDTH`, so the derived comparisons and hash work a whole word at a time.
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
 pub struct Word {
-    length: usize,
+    length: u32,
+    id: u32,
     buffer: [Letter; WIDTH],
 }
 
@@ -24,35 +60,39 @@ impl Word {
     pub fn new() -> Self {
         Word {
             length: 0,
+            id: 0,
             buffer: [Letter::none(); WIDTH],
         }
     }
-    pub fn from_str(input: &str) -> Option<Self> {
+    pub fn from_letters(letters: &[Letter]) -> Self {
+        let inline = letters.len().min(WIDTH);
         let mut buffer = [Letter::none(); WIDTH];
-        let mut length = 0;
-        for c in unidecode(input).chars() {
-            if let Some(l) = Letter::from_unicode(c) {
-                if length == WIDTH {
-                    return None;
-                }
-                buffer[length] = l;
-                length += 1;
-            }
-        }
-        Some(Word {
-            length,
+        buffer[..inline].copy_from_slice(&letters[..inline]);
+        Word {
+            length: letters.len() as u32,
+            id: if letters.len() > WIDTH { intern(letters) } else { 0 },
             buffer,
-        })
+        }
+    }
+    pub fn from_str(input: &str) -> Option<Self> {
+        let letters: Vec<Letter> = unidecode(input).chars().filter_map(Letter::from_unicode).collect();
+        Some(Word::from_letters(&letters))
     }
+    /// Words that grow past `WIDTH` are interned again on every push, so build those
+    /// with `from_letters` or `collect` instead.
     pub fn push(&mut self, letter: Letter) {
-        if self.buffer.len() == self.length {
-            panic!("capacity is full");
+        if self.len() < WIDTH {
+            self.buffer[self.len()] = letter;
+            self.length += 1;
+        } else {
+            let mut letters = self.to_vec();
+            letters.push(letter);
+            *self = Word::from_letters(&letters);
         }
-        self.buffer[self.length] = letter;
-        self.length += 1;
     }
+    #[inline]
     pub fn len(&self) -> usize {
-        self.length
+        self.length as usize
     }
     pub fn from_lines(file: &str) -> io::Result<Vec<Word>> {
         let f = File::open(file)?;
@@ -70,23 +110,39 @@ impl Word {
         self.into_iter().map(|l| l.to_unicode()).collect()
     }
     pub fn into_iter<'a>(&'a self) -> impl 'a + Iterator<Item=Letter> {
-        self.buffer[0..self.length].iter().cloned()
+        self.iter().cloned()
     }
 }
 
 impl Index<usize> for Word {
     type Output = Letter;
 
+    #[inline]
     fn index(&self, index: usize) -> &Self::Output {
-        assert!(index < self.length, "{:?} < {:?}", index, self.length);
-        &self.buffer[index]
+        assert!(index < self.len(), "{:?} < {:?}", index, self.len());
+        if index < WIDTH {
+            &self.buffer[index]
+        } else {
+            &interned(self.id)[index]
+        }
+    }
+}
+
+impl Ord for Word {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.length.cmp(&other.length).then_with(|| {
+            if self.len() <= WIDTH {
+                self.buffer.cmp(&other.buffer)
+            } else {
+                self.deref().cmp(other.deref())
+            }
+        })
     }
 }
 
-impl IndexMut<usize> for Word {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        assert!(index < self.length, "{:?} < {:?}", index, self.length);
-        &mut self.buffer[index]
+impl PartialOrd for Word {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -98,8 +154,8 @@ impl fmt::Debug for Letter {
 
 impl fmt::Debug for Word {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for i in 0..self.length {
-            write!(f, "{:?}", self.buffer[i])?;
+        for letter in self.iter() {
+            write!(f, "{:?}", letter)?;
         }
         Ok(())
     }
@@ -107,18 +163,35 @@ impl fmt::Debug for Word {
 
 impl FromIterator<Letter> for Word {
     fn from_iter<T: IntoIterator<Item=Letter>>(iter: T) -> Self {
-        let mut result = Word::new();
-        for letter in iter {
-            result.push(letter);
-        }
-        result
+        let letters: Vec<Letter> = iter.into_iter().collect();
+        Word::from_letters(&letters)
     }
 }
 
 impl Deref for Word {
     type Target = [Letter];
 
+    #[inline]
     fn deref(&self) -> &Self::Target {
-        &self.buffer[0..self.length]
+        if self.len() <= WIDTH {
+            &self.buffer[..self.len()]
+        } else {
+            interned(self.id)
+        }
     }
 }
+
+#[test]
+fn test_long_word() {
+    let long = "SUPERCALIFRAGILISTICEXPIALIDOCIOUS";
+    let word = Word::from_str(long).unwrap();
+    assert_eq!(word.len(), long.len());
+    assert_eq!(word.to_unicode(), long);
+    assert_eq!(word[20], Letter::from_unicode('E').unwrap());
+    let pushed: Word = word.iter().cloned().collect();
+    assert_eq!(pushed, word);
+    assert_eq!(Word::from_str(&long[..WIDTH]).unwrap().len(), WIDTH);
+    assert!(Word::from_str(&long[..WIDTH]) < Word::from_str(&long[..WIDTH + 1]));
+    assert!(Word::from_str("SUPERCALIFRAGILISTICEXPIALIDOCIOUT") > Word::from_str(long));
+    assert_eq!(std::mem::size_of::<Word>(), 24);
+}
@@ -0,0 +1,296 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{spawn, JoinHandle};
+use std::time::Instant;
+
+use crate::core::puzzle::Cell;
+use crate::fill::search::{Search, Canceled, take_best_result, take_one_result};
+use crate::util::grid::Grid;
+
+const SNAPSHOT_INTERVAL: usize = 256;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Progress {
+    /// The number of calls to `Search::solve` so far.
+    pub nodes: usize,
+    /// The most windows narrowed down to a single word in any search node.
+    pub deepest: usize,
+    /// The grid at the deepest node.
+    pub partial: Grid<Cell>,
+    /// The grid at a recent node.
+    pub current: Grid<Cell>,
+}
+
+/// Shared between a running search and whoever is waiting on it. Attached through
+/// `Search::monitor`, it counts nodes, records partial fills and stops the search
+/// once it is canceled or the deadline passes.
+pub struct Monitor {
+    canceled: AtomicBool,
+    deadline: Option<Instant>,
+    node_limit: Option<usize>,
+    nodes: AtomicUsize,
+    deepest: AtomicUsize,
+    partial: Mutex<Grid<Cell>>,
+    current: Mutex<Grid<Cell>>,
+    parent: Option<Arc<Monitor>>,
+}
+
+impl Monitor {
+    pub fn new(size: (usize, usize), deadline: Option<Instant>) -> Self {
+        Monitor {
+            canceled: AtomicBool::new(false),
+            deadline,
+            node_limit: None,
+            nodes: AtomicUsize::new(0),
+            deepest: AtomicUsize::new(0),
+            partial: Mutex::new(Grid::new(size, |_, _| Cell::Black)),
+            current: Mutex::new(Grid::new(size, |_, _| Cell::Black)),
+            parent: None,
+        }
+    }
+    /// Also stops the search after `limit` nodes, which unlike a deadline gives the same
+    /// answer on every run.
+    pub fn with_node_limit(mut self, limit: usize) -> Self {
+        self.node_limit = Some(limit);
+        self
+    }
+    /// A monitor that can be canceled on its own, but also stops when the parent does
+    /// and reports its progress to the parent.
+    pub fn child(parent: &Arc<Monitor>) -> Self {
+        Monitor {
+            parent: Some(parent.clone()),
+            ..Monitor::new((0, 0), None)
+        }
+    }
+    pub fn cancel(&self) {
+        self.canceled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_canceled(&self) -> bool {
+        self.cancel_requested()
+            || self.deadline.map_or(false, |deadline| Instant::now() >= deadline)
+            || self.parent.as_ref().map_or(false, |parent| parent.is_canceled())
+    }
+    /// Whether `cancel` was called on this monitor or a parent, as opposed to the deadline passing.
+    pub fn cancel_requested(&self) -> bool {
+        self.canceled.load(Ordering::Relaxed)
+            || self.parent.as_ref().map_or(false, |parent| parent.cancel_requested())
+    }
+    pub fn progress(&self) -> Progress {
+        match &self.parent {
+            Some(parent) => parent.progress(),
+            None => {
+                let partial = self.partial.lock().unwrap();
+                Progress {
+                    nodes: self.nodes.load(Ordering::Relaxed),
+                    deepest: self.deepest.load(Ordering::Relaxed),
+                    partial: partial.clone(),
+                    current: self.current.lock().unwrap().clone(),
+                }
+            }
+        }
+    }
+    pub fn check(&self) -> Result<(), Canceled> {
+        if self.is_canceled() {
+            Err(Canceled)
+        } else {
+            Ok(())
+        }
+    }
+    /// Called once per search node. Only the cancel flags are read on every node; the
+    /// deadline and the current grid are looked at every `SNAPSHOT_INTERVAL` nodes.
+    pub fn visit(&self, search: &Search) -> Result<(), Canceled> {
+        if self.cancel_requested() {
+            return Err(Canceled);
+        }
+        if let Some(parent) = &self.parent {
+            return parent.visit(search);
+        }
+        let nodes = self.nodes.fetch_add(1, Ordering::Relaxed);
+        if self.node_limit.map_or(false, |limit| nodes >= limit) {
+            self.nodes.fetch_sub(1, Ordering::Relaxed);
+            return Err(Canceled);
+        }
+        if nodes % SNAPSHOT_INTERVAL == 0 {
+            self.check()?;
+            *self.current.lock().unwrap() = search.finish();
+        }
+        let depth = search.sets.values().filter(|set| set.size() == 1).count();
+        if depth > self.deepest.load(Ordering::Relaxed) {
+            let mut partial = self.partial.lock().unwrap();
+            if depth > self.deepest.load(Ordering::Relaxed) {
+                self.deepest.store(depth, Ordering::Relaxed);
+                *partial = search.finish();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `Search::solve_parallel` on other threads, keeping either the first fill or the
+/// best scoring fill found before the deadline.
+pub struct BackgroundFill {
+    monitor: Arc<Monitor>,
+    finished: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Option<Search>>>,
+}
+
+impl BackgroundFill {
+    /// Keeps the first fill, which for a seeded search is the same on every run. The
+    /// deadline only decides when to give up.
+    pub fn spawn(search: Search, deadline: Option<Instant>, threads: usize) -> Self {
+        Self::spawn_with(search, deadline, threads, false)
+    }
+    /// Keeps the best scoring fill found before the deadline.
+    pub fn spawn_best(search: Search, deadline: Instant, threads: usize) -> Self {
+        Self::spawn_with(search, Some(deadline), threads, true)
+    }
+    fn spawn_with(mut search: Search, deadline: Option<Instant>, threads: usize, best: bool) -> Self {
+        let monitor = Arc::new(Monitor::new(search.sets.grid_size(), deadline));
+        let finished = Arc::new(AtomicBool::new(false));
+        search.monitor = Some(monitor.clone());
+        let finished2 = finished.clone();
+        BackgroundFill {
+            monitor,
+            finished,
+            thread: Some(spawn(move || {
+                let mut result = None;
+                let _ = if best {
+                    search.solve_parallel(threads, &mut take_best_result(&mut result))
+                } else {
+                    search.solve_parallel(threads, &mut take_one_result(&mut result))
+                };
+                finished2.store(true, Ordering::Release);
+                result.map(|mut result| {
+                    result.monitor = None;
+                    result
+                })
+            })),
+        }
+    }
+    pub fn progress(&self) -> Progress {
+        self.monitor.progress()
+    }
+    pub fn cancel(&self) {
+        self.monitor.cancel();
+    }
+    /// Whether the fill was stopped by `cancel`. Running out of time does not count.
+    pub fn is_canceled(&self) -> bool {
+        self.monitor.cancel_requested()
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+    /// Waits for the search to stop and returns the fill it kept.
+    pub fn join(mut self) -> Option<Search> {
+        self.thread.take().unwrap().join().unwrap()
+    }
+}
+
+/// Any other work run on its own thread, polled the same way as a `BackgroundFill`.
+/// Dropping it lets the thread run to the end and throws the result away.
+pub struct Background<T> {
+    finished: Arc<AtomicBool>,
+    thread: JoinHandle<T>,
+}
+
+impl<T: Send + 'static> Background<T> {
+    pub fn spawn(work: impl FnOnce() -> T + Send + 'static) -> Self {
+        let finished = Arc::new(AtomicBool::new(false));
+        let finished2 = finished.clone();
+        Background {
+            finished,
+            thread: spawn(move || {
+                let result = work();
+                finished2.store(true, Ordering::Release);
+                result
+            }),
+        }
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+    pub fn join(self) -> T {
+        self.thread.join().unwrap()
+    }
+}
+
+impl Drop for BackgroundFill {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.monitor.cancel();
+            thread.join().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_search(size: usize) -> Search {
+    use crate::core::puzzle::WindowMap;
+    use crate::fill::scored_list::ScoredWord;
+    use crate::core::letter::Letter;
+    use crate::core::word::Word;
+    let letters: Vec<Letter> = "ABCDEF".chars().map(|c| Letter::from_unicode(c).unwrap()).collect();
+    let mut words = vec![];
+    let mut word = vec![0; size];
+    // Every word over a few letters, so the grid has an enormous number of fills.
+    'outer: loop {
+        words.push(ScoredWord { word: word.iter().map(|&index| letters[index]).collect::<Word>(), score: 50 });
+        for index in word.iter_mut() {
+            *index += 1;
+            if *index < letters.len() {
+                continue 'outer;
+            }
+            *index = 0;
+        }
+        break;
+    }
+    Search::new(WindowMap::from_grid(&Grid::new((size, size), |_, _| true)), &words)
+}
+
+#[test]
+fn test_background_fill() {
+    let fill = BackgroundFill::spawn(test_search(3), None, 2);
+    let result = fill.join().unwrap();
+    assert!(result.sets.values().all(|set| set.size() == 1));
+    assert!(result.monitor.is_none());
+}
+
+#[test]
+fn test_cancel() {
+    let fill = BackgroundFill::spawn_best(test_search(5), Instant::now() + std::time::Duration::from_secs(60), 2);
+    while fill.progress().nodes < 10 && !fill.is_finished() {
+        std::thread::yield_now();
+    }
+    fill.cancel();
+    assert!(fill.is_canceled());
+    assert!(fill.progress().nodes >= 10);
+    fill.join();
+
+    let fill = BackgroundFill::spawn_best(test_search(5), Instant::now(), 2);
+    while !fill.is_finished() {
+        std::thread::yield_now();
+    }
+    assert!(!fill.is_canceled());
+    fill.join();
+}
+
+#[test]
+fn test_seeded_fill() {
+    let fill = |seed| {
+        let mut search = test_search(4);
+        search.seed = Some(seed);
+        BackgroundFill::spawn(search, Some(Instant::now() + std::time::Duration::from_secs(60)), 4).join().unwrap().finish()
+    };
+    assert_eq!(fill(7), fill(7));
+    assert_eq!(fill(8), fill(8));
+}
+
+#[test]
+fn test_node_limit() {
+    let mut search = test_search(5);
+    let monitor = Arc::new(Monitor::new(search.sets.grid_size(), None).with_node_limit(10));
+    search.monitor = Some(monitor.clone());
+    let mut result = None;
+    assert!(search.solve(&mut take_best_result(&mut result)).is_err());
+    assert_eq!(monitor.progress().nodes, 10);
+}
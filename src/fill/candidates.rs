@@ -0,0 +1,99 @@
+use crate::core::puzzle::{Window, WindowMap};
+use crate::fill::scored_list::ScoredWord;
+use crate::fill::search::Search;
+use crate::util::grid::Grid;
+
+/// How many options a partly filled grid leaves for each cell and window.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Candidates {
+    /// The number of letters still possible in each white cell.
+    pub letters: Grid<Option<usize>>,
+    /// The number of words still possible in each window.
+    pub words: WindowMap<usize>,
+}
+
+impl Candidates {
+    pub fn is_stuck(&self, position: (usize, usize)) -> bool {
+        self.letters[position] == Some(0)
+    }
+}
+
+/// A word that fits a window, and how it constrains the windows crossing it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Suggestion {
+    pub word: ScoredWord,
+    /// The fewest words left in any crossing window once this word is placed, or None
+    /// if no window crosses it.
+    pub crossings: Option<usize>,
+}
+
+impl Search {
+    /// The `limit` best scoring words that still fit `window`.
+    pub fn suggestions(&self, window: Window, limit: usize) -> Vec<Suggestion> {
+        self.sets[window].words().iter().take(limit).map(|&word| {
+            let placed = self.branch(window, word, None);
+            Suggestion {
+                word,
+                crossings: window.positions()
+                    .filter_map(|position| self.sets.window_at(position, window.direction().perpendicular()))
+                    .map(|crossing| placed.sets[crossing].size())
+                    .min(),
+            }
+        }).collect()
+    }
+
+    pub fn candidates(&self) -> Candidates {
+        Candidates {
+            letters: Grid::new(self.sets.grid_size(), |x, y| {
+                self.letter_set((x, y)).map(|letters| letters.into_iter().count())
+            }),
+            words: WindowMap::new(self.sets.iter().map(|(window, set)| (window, set.size())), self.sets.grid_size()),
+        }
+    }
+}
+
+#[test]
+fn test_candidates() {
+    use crate::core::puzzle::{Cell, Direction, Window};
+    use crate::core::letter::Letter;
+    use crate::fill::scored_list::scored_words;
+    let words = scored_words(&["AB", "AC", "BD", "CD", "XY"]);
+    let mut search = Search::new(WindowMap::from_grid(&Grid::new((2, 2), |_, _| true)), &words);
+    search.retain(&Grid::new((2, 2), |x, y| match (x, y) {
+        (0, 0) => Cell::White(Letter::from_unicode('A')),
+        _ => Cell::White(None),
+    }));
+    search.refine_all();
+    let candidates = search.candidates();
+    assert_eq!(candidates.letters[(0, 0)], Some(1));
+    assert_eq!(candidates.letters[(1, 0)], Some(2));
+    assert_eq!(candidates.letters[(1, 1)], Some(1));
+    assert_eq!(candidates.words[Window::new((0, 0), 2, Direction::Across)], 2);
+    assert_eq!(candidates.words[Window::new((1, 0), 2, Direction::Down)], 2);
+    assert!(!candidates.is_stuck((1, 1)));
+
+    search.retain(&Grid::new((2, 2), |x, y| match (x, y) {
+        (1, 1) => Cell::White(Letter::from_unicode('Y')),
+        _ => Cell::White(None),
+    }));
+    search.refine_all();
+    assert!(search.candidates().is_stuck((1, 1)));
+}
+
+#[test]
+fn test_suggestions() {
+    use crate::core::puzzle::{Cell, Direction};
+    use crate::core::letter::Letter;
+    use crate::fill::scored_list::scored;
+    let words = [scored("AB", 90), scored("AC", 50), scored("AD", 10), scored("BE", 50), scored("BF", 50), scored("CE", 50)];
+    let mut search = Search::new(WindowMap::from_grid(&Grid::new((2, 2), |_, _| true)), &words);
+    search.retain(&Grid::new((2, 2), |x, y| match (x, y) {
+        (0, 0) => Cell::White(Letter::from_unicode('A')),
+        _ => Cell::White(None),
+    }));
+    let across = Window::new((0, 0), 2, Direction::Across);
+    let suggestions = search.suggestions(across, 10);
+    assert_eq!(suggestions.iter().map(|suggestion| suggestion.word.word.to_unicode()).collect::<Vec<_>>(), vec!["AB", "AC", "AD"]);
+    assert_eq!(suggestions.iter().map(|suggestion| suggestion.crossings).collect::<Vec<_>>(), vec![Some(1), Some(1), Some(0)]);
+    assert_eq!(search.suggestions(across, 1).len(), 1);
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::core::puzzle::Window;
+use crate::fill::background::Monitor;
+use crate::fill::search::{Search, take_one_result};
+
+/// Part of a grid that cannot be filled on its own. Removing any one of the windows
+/// makes the rest fillable, unless the search ran out of time while checking.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Conflict {
+    pub windows: Vec<Window>,
+    /// Every cell of the conflicting windows.
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Search {
+    /// Some(true) if the windows in `subset` have no fill, Some(false) if they do and
+    /// None if the deadline passed first.
+    fn unsatisfiable(&self, subset: &BTreeSet<Window>, deadline: Instant) -> Option<bool> {
+        let mut sub = self.filter(&mut |window| subset.contains(&window));
+        let monitor = Arc::new(Monitor::new(sub.sets.grid_size(), Some(deadline)));
+        sub.monitor = Some(monitor.clone());
+        sub.refine_all();
+        if sub.sets.values().any(|set| set.size() == 0) {
+            return Some(true);
+        }
+        let mut result = None;
+        let _ = sub.solve(&mut take_one_result(&mut result));
+        if result.is_some() {
+            Some(false)
+        } else if monitor.is_canceled() {
+            None
+        } else {
+            Some(true)
+        }
+    }
+
+    fn crossings(&self, window: Window) -> Vec<Window> {
+        window.positions().filter_map(|position| self.sets.window_at(position, window.direction().perpendicular())).collect()
+    }
+
+    /// Explains why the search has no fill. Starting from the windows that `refine_all`
+    /// empties, or from the most constrained window if propagation alone finds nothing,
+    /// grows a neighbourhood of crossing windows until it cannot be filled, then drops
+    /// windows that are not needed. Returns None if a fill exists or the deadline
+    /// passes before the search can tell.
+    pub fn explain(&self, deadline: Instant) -> Option<Conflict> {
+        let mut refined = self.clone();
+        refined.monitor = None;
+        refined.refine_all();
+        let mut core: BTreeSet<Window> = refined.sets.iter()
+            .filter(|(_, set)| set.size() == 0)
+            .map(|(window, _)| window)
+            .collect();
+        if core.is_empty() {
+            if !self.unsatisfiable(&self.sets.windows().collect(), deadline)? {
+                return None;
+            }
+            core.extend(refined.branch_window());
+        }
+        while !self.unsatisfiable(&core, deadline)? {
+            let grown: BTreeSet<Window> = core.iter()
+                .flat_map(|&window| self.crossings(window))
+                .chain(core.iter().cloned())
+                .collect();
+            if grown.len() == core.len() {
+                core = self.sets.windows().collect();
+                break;
+            }
+            core = grown;
+        }
+        for window in core.clone() {
+            core.remove(&window);
+            if !self.unsatisfiable(&core, deadline)? {
+                core.insert(window);
+            }
+        }
+        let cells: BTreeSet<(usize, usize)> = core.iter().flat_map(|window| window.positions()).collect();
+        Some(Conflict {
+            windows: core.into_iter().collect(),
+            cells: cells.into_iter().collect(),
+        })
+    }
+}
+
+#[test]
+fn test_explain() {
+    use crate::core::puzzle::{WindowMap, Cell, Direction};
+    use crate::core::letter::Letter;
+    use crate::fill::scored_list::{ScoredWord, scored, scored_words};
+    use crate::util::grid::Grid;
+    use std::time::Duration;
+    let words = scored_words(&["ABC", "BCA", "CAB", "AAA", "BBB", "CCC"]);
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let search = Search::new(WindowMap::from_grid(&Grid::new((3, 3), |_, _| true)), &words);
+    assert_eq!(search.explain(deadline), None);
+
+    // No word starts with "AB", so the seeded entry can't be completed but everything else can.
+    let letters = ["A", "B", "C"];
+    let most: Vec<ScoredWord> = iproduct!(&letters, &letters, &letters)
+        .map(|(a, b, c)| format!("{}{}{}", a, b, c))
+        .filter(|word| !word.starts_with("AB"))
+        .map(|word| scored(&word, 50))
+        .collect();
+    let mut seeded = Search::new(WindowMap::from_grid(&Grid::new((3, 3), |_, _| true)), &most);
+    seeded.retain(&Grid::new((3, 3), |x, y| match (x, y) {
+        (0, 0) => Cell::White(Letter::from_unicode('A')),
+        (1, 0) => Cell::White(Letter::from_unicode('B')),
+        _ => Cell::White(None),
+    }));
+    let conflict = seeded.explain(deadline).unwrap();
+    assert_eq!(conflict.windows, vec![Window::new((0, 0), 3, Direction::Across)]);
+    assert_eq!(conflict.cells, vec![(0, 0), (1, 0), (2, 0)]);
+
+    // Each window has a word on its own, but "A?" only allows "AX" and "?Z" only "YZ",
+    // which disagree where they cross.
+    let pairs = scored_words(&["AX", "YZ", "BB", "BC", "CB", "CC"]);
+    let mut crossing = Search::new(WindowMap::from_grid(&Grid::new((2, 2), |_, _| true)), &pairs);
+    crossing.retain(&Grid::new((2, 2), |x, y| match (x, y) {
+        (0, 0) => Cell::White(Letter::from_unicode('A')),
+        (1, 1) => Cell::White(Letter::from_unicode('Z')),
+        _ => Cell::White(None),
+    }));
+    assert!(crossing.sets.values().all(|set| set.size() > 0));
+    let conflict = crossing.explain(deadline).unwrap();
+    assert_eq!(conflict.windows.len(), 2);
+    assert_ne!(conflict.windows[0].direction(), conflict.windows[1].direction());
+    assert_eq!(conflict.cells.len(), 3);
+    assert_eq!(crossing.explain(Instant::now()), None);
+}
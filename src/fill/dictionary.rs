@@ -1,17 +1,18 @@
 use crate::core::word::Word;
+use crate::fill::scored_list::ScoredWord;
 use std::collections::HashMap;
 use std::fs::File;
 use csv::{ReaderBuilder, WriterBuilder};
 use std::io;
 
 pub struct EditedDictionary {
-    source: Vec<Word>,
+    source: Vec<ScoredWord>,
     update_file: String,
     updates: HashMap<Word, bool>,
 }
 
 impl EditedDictionary {
-    pub fn new(source: Vec<Word>, update_file: &str) -> Self {
+    pub fn new(source: Vec<ScoredWord>, update_file: &str) -> Self {
         EditedDictionary {
             source,
             update_file: update_file.to_string(),
@@ -44,7 +45,7 @@ impl EditedDictionary {
         }
     }
 
-    pub fn build(&self) -> Vec<Word> {
-        self.source.iter().filter(|word| self.updates.get(word) != Some(&false)).cloned().collect()
+    pub fn build(&self) -> Vec<ScoredWord> {
+        self.source.iter().filter(|word| self.updates.get(&word.word) != Some(&false)).cloned().collect()
     }
 }
@@ -2,4 +2,9 @@ pub mod scored_list;
 pub mod word_set;
 pub mod search;
 pub mod trie;
-pub mod dictionary;
\ No newline at end of file
+pub mod dictionary;
+pub mod background;
+pub mod parallel;
+pub mod conflict;
+pub mod candidates;
+pub mod pattern;
@@ -0,0 +1,218 @@
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::core::letter::Letter;
+use crate::core::puzzle::Window;
+use crate::fill::background::Monitor;
+use crate::fill::scored_list::ScoredWord;
+use crate::fill::search::{Search, Canceled, take_one_result};
+
+type Result = std::result::Result<(), Canceled>;
+
+/// How many fills a job may get ahead of the callback before its thread waits.
+const FOUND_BUFFER: usize = 4;
+
+/// The work below the top node of a search, cut into jobs that can run in any order.
+enum Plan {
+    /// One job per branch of `solve_direct`, each producing every fill of its branch.
+    Direct {
+        search: Search,
+        window: Window,
+        words: Vec<(ScoredWord, Option<u64>)>,
+    },
+    /// Two jobs per assignment of the overlap in `solve_split`, one for the first fill
+    /// of each half.
+    Split {
+        overlap: Vec<(usize, usize)>,
+        values: Vec<Vec<Letter>>,
+        children: [Search; 2],
+    },
+}
+
+impl Plan {
+    fn jobs(&self) -> usize {
+        match self {
+            Plan::Direct { words, .. } => words.len(),
+            Plan::Split { values, .. } => values.len() * 2,
+        }
+    }
+    fn run(&self, job: usize, monitor: &Arc<Monitor>, found: &mut dyn FnMut(Search) -> Result) -> Result {
+        match self {
+            Plan::Direct { search, window, words } => {
+                let (word, seed) = words[job];
+                let mut branch = search.branch(*window, word, seed);
+                branch.monitor = Some(monitor.clone());
+                branch.solve(found)
+            }
+            Plan::Split { overlap, values, children } => {
+                let mut half = children[job % 2].restrict_overlap(overlap, &values[job / 2]);
+                half.monitor = Some(monitor.clone());
+                let mut result = None;
+                let _ = half.solve(&mut take_one_result(&mut result));
+                match result {
+                    Some(result) => found(result),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Search {
+    /// Like `solve`, but spreads the branches below this node over `threads` threads.
+    /// The callback still runs on the calling thread and sees the same fills in the
+    /// same order as with `solve`; returning `Err(Canceled)` stops every thread.
+    pub fn solve_parallel(&self, threads: usize, callback: &mut dyn FnMut(Search) -> Result) -> Result {
+        if threads <= 1 {
+            return self.solve(callback);
+        }
+        if let Some(monitor) = &self.monitor {
+            monitor.visit(self)?;
+        }
+        if self.sets.values().any(|set| set.size() == 0) {
+            return Ok(());
+        }
+        let plan = match self.useful_split() {
+            Some((overlap, children)) => {
+                let values = self.overlap_values(&overlap);
+                Plan::Split { overlap, values, children }
+            }
+            None => match self.branch_window() {
+                None => return callback(self.clone()),
+                Some(window) => Plan::Direct {
+                    search: self.clone(),
+                    window,
+                    words: self.branch_order(window),
+                },
+            }
+        };
+        let plan = Arc::new(plan);
+        let monitor = Arc::new(match &self.monitor {
+            Some(parent) => Monitor::child(parent),
+            None => Monitor::new(self.sets.grid_size(), None),
+        });
+        let next_job = Arc::new(AtomicUsize::new(0));
+        // Each job has its own small channel, hung up once the job is over, so workers
+        // on later jobs wait for the callback instead of piling up fills.
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..plan.jobs()).map(|_| mpsc::sync_channel(FOUND_BUFFER)).unzip();
+        let senders = Arc::new(Mutex::new(senders.into_iter().map(Some).collect::<Vec<_>>()));
+        let workers: Vec<_> = (0..threads).map(|_| {
+            let plan = plan.clone();
+            let monitor = monitor.clone();
+            let next_job = next_job.clone();
+            let senders = senders.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    if job >= plan.jobs() {
+                        return;
+                    }
+                    if monitor.is_canceled() {
+                        senders.lock().unwrap().iter_mut().for_each(|sender| *sender = None);
+                        return;
+                    }
+                    let sender = senders.lock().unwrap()[job].take();
+                    if let Some(sender) = sender {
+                        let _ = plan.run(job, &monitor, &mut |found| sender.send(found).map_err(|_| Canceled));
+                    }
+                }
+            })
+        }).collect();
+
+        let mut deliver = || -> Result {
+            match &*plan {
+                Plan::Direct { .. } => {
+                    for receiver in receivers.iter() {
+                        for mut result in receiver.iter() {
+                            result.monitor = self.monitor.clone();
+                            callback(result)?;
+                        }
+                        monitor.check()?;
+                    }
+                }
+                Plan::Split { .. } => {
+                    for pair in receivers.chunks(2) {
+                        if let (Ok(r1), Ok(r2)) = (pair[0].recv(), pair[1].recv()) {
+                            callback(self.combine(&r1, &r2))?;
+                        }
+                        monitor.check()?;
+                    }
+                }
+            }
+            Ok(())
+        };
+        let result = deliver();
+        monitor.cancel();
+        drop(receivers);
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+fn test_words(alphabet: &str, lengths: &[usize]) -> Vec<ScoredWord> {
+    use crate::core::word::Word;
+    let mut words: Vec<Word> = vec![Word::new()];
+    let mut result = vec![];
+    for length in 1..=*lengths.iter().max().unwrap() {
+        words = words.iter()
+            .flat_map(|word| alphabet.chars().map(move |c| {
+                let mut longer = *word;
+                longer.push(Letter::from_unicode(c).unwrap());
+                longer
+            }))
+            .collect();
+        if lengths.contains(&length) {
+            result.extend(words.iter().enumerate().map(|(index, &word)| ScoredWord { word, score: (index % 7) as u8 }));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+fn first_results(limit: usize, solve: &dyn Fn(&mut dyn FnMut(Search) -> Result) -> Result) -> Vec<crate::util::grid::Grid<crate::core::puzzle::Cell>> {
+    let mut results = vec![];
+    let _ = solve(&mut |result: Search| {
+        assert!(result.monitor.is_none());
+        results.push(result.finish());
+        if results.len() == limit { Err(Canceled) } else { Ok(()) }
+    });
+    results
+}
+
+#[test]
+fn test_parallel_direct() {
+    use crate::core::puzzle::WindowMap;
+    use crate::util::grid::Grid;
+    use crate::fill::search::take_all_results;
+    let search = Search::new(WindowMap::from_grid(&Grid::new((3, 3), |_, _| true)), &test_words("AB", &[3]));
+    assert!(search.useful_split().is_none());
+    let mut serial = vec![];
+    let _ = search.solve(&mut take_all_results(&mut serial));
+    let mut parallel = vec![];
+    let _ = search.solve_parallel(4, &mut take_all_results(&mut parallel));
+    assert!(!serial.is_empty());
+    assert_eq!(serial.iter().map(|s| s.finish()).collect::<Vec<_>>(), parallel.iter().map(|s| s.finish()).collect::<Vec<_>>());
+    assert_eq!(first_results(3, &|callback| search.solve(callback)),
+               first_results(3, &|callback| search.solve_parallel(4, callback)));
+}
+
+#[test]
+fn test_parallel_split() {
+    use crate::core::puzzle::WindowMap;
+    use crate::util::grid::Grid;
+    // Two 4x4 blocks joined only through a two letter down entry.
+    let white = Grid::new((9, 8), |x, y| {
+        (x < 4 && y < 4) || (x > 4 && y > 3) || (x == 4 && (y == 3 || y == 4))
+    });
+    let search = Search::new(WindowMap::from_grid(&white), &test_words("ABC", &[2, 4, 5]));
+    assert!(search.useful_split().is_some());
+    let serial = first_results(100, &|callback| search.solve(callback));
+    assert!(!serial.is_empty());
+    assert_eq!(serial, first_results(100, &|callback| search.solve_parallel(4, callback)));
+    assert_eq!(first_results(2, &|callback| search.solve(callback)),
+               first_results(2, &|callback| search.solve_parallel(4, callback)));
+}
@@ -0,0 +1,266 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::core::letter::Letter;
+use crate::core::puzzle::{Cell, Direction, WindowMap, MIN_LENGTH, regions, short_runs};
+use crate::core::word::Word;
+use crate::fill::background::Monitor;
+use crate::fill::scored_list::ScoredWord;
+use crate::fill::search::{Search, take_one_result};
+use crate::util::grid::Grid;
+
+/// How many search nodes to spend on filling one candidate pattern before trying another.
+const FILL_ATTEMPT_NODES: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+    /// Add black squares until there are at least this many entries.
+    Entries(usize),
+    /// Add black squares until there are at least this many.
+    Blocks(usize),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ThemeEntry {
+    pub word: Word,
+    /// Where the entry starts and which way it runs, or None to place it anywhere.
+    pub placement: Option<((usize, usize), Direction)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PatternOptions {
+    pub size: (usize, usize),
+    pub target: Target,
+    pub theme: Vec<ThemeEntry>,
+    pub seed: u64,
+}
+
+
+/// A pattern under construction. `black` always has rotational symmetry.
+#[derive(Clone)]
+struct Layout {
+    black: Grid<bool>,
+    letters: Grid<Option<Letter>>,
+}
+
+impl Layout {
+    fn new(size: (usize, usize)) -> Self {
+        Layout {
+            black: Grid::new(size, |_, _| false),
+            letters: Grid::new(size, |_, _| None),
+        }
+    }
+
+    fn partner(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        (self.black.size().0 - 1 - x, self.black.size().1 - 1 - y)
+    }
+
+    fn set_black(&mut self, position: (usize, usize)) -> bool {
+        let partner = self.partner(position);
+        if self.letters[position].is_some() || self.letters[partner].is_some() {
+            return false;
+        }
+        self.black[position] = true;
+        self.black[partner] = true;
+        true
+    }
+
+    fn cells(&self, length: usize, (x, y): (usize, usize), direction: Direction) -> Option<Vec<(usize, usize)>> {
+        let (width, height) = self.black.size();
+        match direction {
+            Direction::Across if x + length <= width && y < height => Some((x..x + length).map(|x| (x, y)).collect()),
+            Direction::Down if y + length <= height && x < width => Some((y..y + length).map(|y| (x, y)).collect()),
+            _ => None,
+        }
+    }
+
+    /// The cells just before and after an entry, which must be black.
+    fn ends(&self, length: usize, (x, y): (usize, usize), direction: Direction) -> Vec<(usize, usize)> {
+        let (width, height) = self.black.size();
+        let mut ends = vec![];
+        match direction {
+            Direction::Across => {
+                if x > 0 {
+                    ends.push((x - 1, y));
+                }
+                if x + length < width {
+                    ends.push((x + length, y));
+                }
+            }
+            Direction::Down => {
+                if y > 0 {
+                    ends.push((x, y - 1));
+                }
+                if y + length < height {
+                    ends.push((x, y + length));
+                }
+            }
+        }
+        ends
+    }
+
+    fn place(&mut self, word: Word, position: (usize, usize), direction: Direction) -> bool {
+        let cells = match self.cells(word.len(), position, direction) {
+            Some(cells) => cells,
+            None => return false,
+        };
+        for (&cell, letter) in cells.iter().zip(word.into_iter()) {
+            if self.black[cell] || self.letters[cell].map_or(false, |old| old != letter) {
+                return false;
+            }
+            self.letters[cell] = Some(letter);
+        }
+        self.ends(word.len(), position, direction).into_iter().all(|end| self.set_black(end))
+    }
+
+    fn white(&self) -> Grid<bool> {
+        Grid::new(self.black.size(), |x, y| !self.black[(x, y)])
+    }
+
+    /// Blacks out runs too short to be entries, as long as they hold no theme letters.
+    fn repair(&mut self) -> bool {
+        loop {
+            let runs = short_runs(&self.white());
+            if runs.is_empty() {
+                return true;
+            }
+            for cell in runs.into_iter().flatten() {
+                if !self.set_black(cell) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        let white = self.white();
+        short_runs(&white).is_empty() && regions(&white).len() == 1
+    }
+
+    fn reached(&self, target: Target) -> bool {
+        match target {
+            Target::Entries(entries) => WindowMap::from_grid(&self.white()).len() >= entries,
+            Target::Blocks(blocks) => self.black.iter().filter(|&&black| black).count() >= blocks,
+        }
+    }
+
+    fn to_grid(&self) -> Grid<Cell> {
+        Grid::new(self.black.size(), |x, y| {
+            if self.black[(x, y)] {
+                Cell::Black
+            } else {
+                Cell::White(self.letters[(x, y)])
+            }
+        })
+    }
+}
+
+fn attempt(options: &PatternOptions, rng: &mut StdRng) -> Option<Layout> {
+    let (width, height) = options.size;
+    let mut layout = Layout::new(options.size);
+    let mut positions: Vec<(usize, usize)> = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).collect();
+    for entry in options.theme.iter() {
+        let mut placements = match entry.placement {
+            Some(placement) => vec![placement],
+            None => positions.iter()
+                .flat_map(|&position| vec![(position, Direction::Across), (position, Direction::Down)])
+                .collect(),
+        };
+        placements.shuffle(rng);
+        layout = placements.into_iter().find_map(|(position, direction)| {
+            let mut placed = layout.clone();
+            if placed.place(entry.word, position, direction) { Some(placed) } else { None }
+        })?;
+    }
+    if !layout.repair() || !layout.is_valid() {
+        return None;
+    }
+    positions.shuffle(rng);
+    while !layout.reached(options.target) {
+        layout = positions.iter().find_map(|&position| {
+            if layout.black[position] {
+                return None;
+            }
+            let mut next = layout.clone();
+            if next.set_black(position) && next.is_valid() { Some(next) } else { None }
+        })?;
+    }
+    Some(layout)
+}
+
+fn fillable(layout: &Layout, words: &[ScoredWord], deadline: Instant) -> bool {
+    let grid = layout.to_grid();
+    let mut search = Search::new(WindowMap::from_grid(&layout.white()), words);
+    search.retain(&grid);
+    search.refine_all();
+    search.monitor = Some(Arc::new(Monitor::new(grid.size(), Some(deadline)).with_node_limit(FILL_ATTEMPT_NODES)));
+    let mut result = None;
+    let _ = search.solve(&mut take_one_result(&mut result));
+    result.is_some()
+}
+
+/// Places black squares with rotational symmetry around the theme entries, keeping every
+/// white cell connected and in an across and a down entry of at least `MIN_LENGTH`
+/// letters. Returns the first pattern that `Search` can fill from `words` and the theme
+/// entries, with the theme letters filled in, or None if none turns up by the deadline.
+pub fn generate_pattern(options: &PatternOptions, words: &[ScoredWord], deadline: Instant) -> Option<Grid<Cell>> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut dictionary = words.to_vec();
+    dictionary.extend(options.theme.iter().map(|entry| ScoredWord { word: entry.word, score: u8::MAX }));
+    while Instant::now() < deadline {
+        if let Some(layout) = attempt(options, &mut rng) {
+            if fillable(&layout, &dictionary, deadline) {
+                return Some(layout.to_grid());
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn test_generate_pattern() {
+    let mut words = vec![];
+    let mut current = vec![Word::new()];
+    for length in 1..=7 {
+        current = current.iter().flat_map(|word| "AB".chars().map(move |c| {
+            let mut longer = *word;
+            longer.push(Letter::from_unicode(c).unwrap());
+            longer
+        })).collect();
+        if length >= MIN_LENGTH {
+            words.extend(current.iter().map(|&word| ScoredWord { word, score: 50 }));
+        }
+    }
+    let theme = |word: &str, placement| ThemeEntry { word: Word::from_str(word).unwrap(), placement };
+    let options = PatternOptions {
+        size: (7, 7),
+        target: Target::Blocks(6),
+        theme: vec![theme("BABBA", None), theme("AAB", Some(((0, 0), Direction::Across)))],
+        seed: 3,
+    };
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let grid = generate_pattern(&options, &words, deadline).unwrap();
+    let white = Grid::new(grid.size(), |x, y| grid[(x, y)] != Cell::Black);
+    for y in 0..7 {
+        for x in 0..7 {
+            assert_eq!(white[(x, y)], white[(6 - x, 6 - y)]);
+        }
+    }
+    assert!(short_runs(&white).is_empty());
+    assert_eq!(regions(&white).len(), 1);
+    assert!(white.iter().filter(|&&white| !white).count() >= 6);
+    assert_eq!(grid[(0, 0)], Cell::White(Letter::from_unicode('A')));
+    assert_eq!(grid[(3, 0)], Cell::Black);
+    let entries: Vec<String> = WindowMap::from_grid(&white).windows()
+        .map(|window| window.positions().map(|position| match grid[position] {
+            Cell::White(Some(letter)) => letter.to_unicode(),
+            _ => '.',
+        }).collect())
+        .collect();
+    assert!(entries.contains(&"BABBA".to_string()));
+    assert_eq!(generate_pattern(&options, &words, deadline), Some(grid));
+}
@@ -6,6 +6,12 @@ use std::io;
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 
 use crate::core::word::Word;
+use csv::ReaderBuilder;
+use std::path::Path;
+
+pub const DEFAULT_LIST: &str = "dictionaries/Default.lst";
+/// The score given to words from lists that don't have scores.
+pub const DEFAULT_SCORE: u8 = 50;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct ScoredWord {
@@ -54,11 +60,96 @@ impl ScoredWord {
         Ok(words)
     }
     pub fn default() -> io::Result<Vec<Self>> {
-        let mut f = File::open("dictionaries/Default.lst")?;
+        let mut f = File::open(DEFAULT_LIST)?;
         Self::read(&mut f)
     }
+
+    fn parse_score(score: &str, line: &str) -> io::Result<u8> {
+        let score: u32 = score.trim().parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("bad score in {:?}", line))
+        })?;
+        Ok(score.min(u8::MAX as u32) as u8)
+    }
+
+    /// Reads `word;score` lines, as used by Spread the Wordlist and similar lists. Lines
+    /// without a score, as in plain one word per line lists, get `default_score`.
+    pub fn read_scored_lines(read: &mut dyn BufRead, default_score: u8) -> io::Result<Vec<Self>> {
+        let mut words = vec![];
+        for line in read.lines() {
+            let line = line?;
+            let (word, score) = match line.rfind(';') {
+                Some(index) => (&line[..index], Self::parse_score(&line[index + 1..], &line)?),
+                None => (line.as_str(), default_score),
+            };
+            if let Some(word) = Word::from_str(word) {
+                if word.len() > 0 {
+                    words.push(ScoredWord { word, score });
+                }
+            }
+        }
+        Ok(words)
+    }
+
+    /// Reads a CSV list with the word in the first column and an optional score in the second.
+    pub fn read_csv(read: &mut dyn Read, default_score: u8) -> io::Result<Vec<Self>> {
+        let mut words = vec![];
+        for record in ReaderBuilder::new().has_headers(false).flexible(true).from_reader(read).records() {
+            let record = record?;
+            let score = match record.get(1) {
+                Some(score) if !score.trim().is_empty() => Self::parse_score(score, &record[0])?,
+                _ => default_score,
+            };
+            if let Some(word) = record.get(0).and_then(Word::from_str) {
+                if word.len() > 0 {
+                    words.push(ScoredWord { word, score });
+                }
+            }
+        }
+        Ok(words)
+    }
+
+    /// Loads a word list, choosing the format from the extension: `.lst` for the binary
+    /// format of `read`, `.csv` for `read_csv` and anything else for `read_scored_lines`.
+    pub fn load(path: &str) -> io::Result<Vec<Self>> {
+        let mut file = File::open(path)?;
+        match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+            Some("lst") => Self::read(&mut file),
+            Some("csv") => Self::read_csv(&mut file, DEFAULT_SCORE),
+            _ => Self::read_scored_lines(&mut BufReader::new(file), DEFAULT_SCORE),
+        }
+    }
+
+    /// Combines several lists into one sorted by descending score. A word listed in more
+    /// than one source takes its score from the earliest, so later sources only add words.
+    pub fn merge(sources: Vec<Vec<Self>>) -> Vec<Self> {
+        let mut merged: HashMap<Word, u8> = HashMap::new();
+        for word in sources.into_iter().flatten() {
+            merged.entry(word.word).or_insert(word.score);
+        }
+        let mut words: Vec<Self> = merged.into_iter().map(|(word, score)| ScoredWord { word, score }).collect();
+        words.sort_by_key(|word| (-(word.score as i32), word.word));
+        words
+    }
+
+    /// Loads and merges the lists at `paths`, in order of priority, or the default list
+    /// if there are none.
+    pub fn load_all(paths: &[String]) -> io::Result<Vec<Self>> {
+        if paths.is_empty() {
+            return Self::default();
+        }
+        Ok(Self::merge(paths.iter().map(|path| Self::load(path)).collect::<io::Result<_>>()?))
+    }
+}
+
+#[cfg(test)]
+pub fn scored(word: &str, score: u8) -> ScoredWord {
+    ScoredWord { word: Word::from_str(word).unwrap(), score }
 }
 
+#[cfg(test)]
+pub fn scored_words(words: &[&str]) -> Vec<ScoredWord> {
+    words.iter().map(|word| scored(word, DEFAULT_SCORE)).collect()
+}
 
 #[test]
 fn test() {
@@ -69,4 +160,24 @@ fn test() {
             println!("{:?}", word);
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_read_lists() {
+    let lines = b"apple;60\nBANANA PEEL;25\nplain\n\nhigh;300\n";
+    assert_eq!(ScoredWord::read_scored_lines(&mut &lines[..], 50).unwrap(),
+               vec![scored("APPLE", 60), scored("BANANAPEEL", 25), scored("PLAIN", 50), scored("HIGH", 255)]);
+    assert!(ScoredWord::read_scored_lines(&mut &b"word;x\n"[..], 50).is_err());
+    let csv = b"cherry,40\ndate\n\"fig, dried\",10\n";
+    assert_eq!(ScoredWord::read_csv(&mut &csv[..], 50).unwrap(),
+               vec![scored("CHERRY", 40), scored("DATE", 50), scored("FIGDRIED", 10)]);
+}
+
+#[test]
+fn test_merge() {
+    let merged = ScoredWord::merge(vec![
+        vec![scored("AB", 10), scored("CD", 60)],
+        vec![scored("AB", 90), scored("EF", 30), scored("GH", 60)],
+    ]);
+    assert_eq!(merged, vec![scored("CD", 60), scored("GH", 60), scored("EF", 30), scored("AB", 10)]);
+}
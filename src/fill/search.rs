@@ -4,15 +4,26 @@ use std::collections::{HashSet, HashMap};
 use crate::core::letter::{Letter, LetterSet};
 use crate::core::word::Word;
 use crate::util::grid::Grid;
-use rand::distributions::{Bernoulli, Distribution};
 use crate::util::graph::{Graph, stoer_wagner};
 use std::fmt;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::cmp::Reverse;
 use crate::util::product::CartesianProduct;
+use crate::fill::scored_list::ScoredWord;
+use std::time::Instant;
+use std::sync::Arc;
+use crate::fill::background::Monitor;
 
 #[derive(Clone)]
 pub struct Search {
     pub sets: WindowMap<WordSet>,
+    pub monitor: Option<Arc<Monitor>>,
+    /// Shuffles words of equal score before `solve_direct` tries them. Each branch
+    /// gets its own seed drawn from its parent's, so a given seed always produces the
+    /// same fills in the same order.
+    pub seed: Option<u64>,
 }
 
 pub struct Canceled;
@@ -33,6 +44,17 @@ pub fn take_all_results<'a, T>(result: &'a mut Vec<T>) -> impl 'a + FnMut(T) ->
     }
 }
 
+/// Keeps the highest scoring fill seen so far. This never stops the search, so for the
+/// best fill within some time the search needs a `Monitor` with a deadline.
+pub fn take_best_result<'a>(result: &'a mut Option<Search>) -> impl 'a + FnMut(Search) -> Result {
+    move |value| {
+        if result.as_ref().map_or(true, |best| value.score() > best.score()) {
+            *result = Some(value);
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Debug for Search {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for (i, (window, set)) in self.sets.iter().enumerate() {
@@ -47,15 +69,29 @@ impl fmt::Debug for Search {
 }
 
 impl Search {
-    pub fn new(windows: WindowMap<()>, words: &[Word]) -> Self {
+    pub fn new(windows: WindowMap<()>, words: &[ScoredWord]) -> Self {
+        let no_rebus = Grid::new(windows.grid_size(), |_, _| None);
+        Self::with_rebus(windows, words, &no_rebus)
+    }
+
+    /// Like `new`, but cells in `rebus` hold a fixed run of letters, or none for a symbol.
+    /// Windows through them are filled with words that spell the run out in full.
+    pub fn with_rebus(windows: WindowMap<()>, words: &[ScoredWord], rebus: &Grid<Option<Word>>) -> Self {
         Search {
             sets: WindowMap::new(
                 windows
                     .windows().map(
                     |window| {
-                        (window, WordSet::from_words(words, window.length()))
+                        let cells: Vec<Option<Word>> = window.positions().map(|position| rebus[position]).collect();
+                        if cells.iter().all(|cell| cell.is_none()) {
+                            (window, WordSet::from_words(words, window.length()))
+                        } else {
+                            (window, WordSet::from_rebus_words(words, &cells))
+                        }
                     }
-                ), windows.grid_size())
+                ), windows.grid_size()),
+            monitor: None,
+            seed: None,
         }
     }
 
@@ -92,6 +128,21 @@ impl Search {
         }
     }
 
+    /// Windows already narrowed to a single word keep it, so entries typed in by hand survive.
+    pub fn retain_min_score(&mut self, min_score: u8) {
+        for set in self.sets.iter_mut().map(|(_, set)| set) {
+            if set.size() > 1 {
+                set.retain_scored(|word| word.score >= min_score);
+            }
+        }
+    }
+
+    /// The total score of the best remaining word in every window, which is the score of
+    /// the fill once every window has a single word.
+    pub fn score(&self) -> usize {
+        self.sets.values().map(|set| set.best_score().unwrap_or(0) as usize).sum()
+    }
+
     pub fn refine_all(&mut self) {
         self.refine(self.sets.windows().collect());
     }
@@ -179,7 +230,9 @@ impl Search {
                     .filter(|(window, set)| condition(*window))
                     .map(|(window, set)| (window, set.clone()))
                 ,
-                self.sets.grid_size())
+                self.sets.grid_size()),
+            monitor: self.monitor.clone(),
+            seed: self.seed,
         }
     }
 
@@ -233,26 +286,52 @@ impl Search {
     }
 
 
+    /// The undecided window with the fewest candidates, which `solve_direct` branches on.
+    pub fn branch_window(&self) -> Option<Window> {
+        self.sets.iter()
+            .filter(|(window, set)| set.size() > 1)
+            .min_by_key(|(window, set)| set.size())
+            .map(|(window, set)| window)
+    }
+
+    /// The words `solve_direct` tries in `window`, in order, with the seed for each branch.
+    pub fn branch_order(&self, window: Window) -> Vec<(ScoredWord, Option<u64>)> {
+        let words = self.sets[window].words();
+        match self.seed {
+            None => words.iter().map(|&word| (word, None)).collect(),
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut order = words.to_vec();
+                order.shuffle(&mut rng);
+                order.sort_by_key(|word| Reverse(word.score));
+                order.into_iter().map(|word| (word, Some(rng.gen()))).collect()
+            }
+        }
+    }
+
+    pub fn branch(&self, window: Window, word: ScoredWord, seed: Option<u64>) -> Search {
+        let mut search2: Search = self.clone();
+        search2.seed = seed;
+        search2.sets[window] = WordSet::from_words(&[word], word.word.len());
+        for (window2, set2) in search2.sets.iter_mut() {
+            if window2 != window {
+                set2.retain(|word2| word2 != word.word);
+            }
+        }
+        search2.refine_one(window);
+        search2
+    }
+
     pub fn solve_direct(&self, callback: &mut dyn FnMut(Search) -> Result) -> Result {
         let window =
-            match self.sets.iter()
-                .filter(|(window, set)| set.size() > 1)
-                .min_by_key(|(window, set)| set.size()) {
+            match self.branch_window() {
                 None => {
                     return callback((*self).clone());
                 }
-                Some((index, set)) => index,
+                Some(window) => window,
             };
-        for &word in self.sets[window].words() {
-            let mut search2: Search = self.clone();
-            search2.sets[window] = WordSet::from_words(&[word], word.len());
-            for (window2, set2) in search2.sets.iter_mut() {
-                if window2 != window {
-                    set2.retain(|word2| word2 != word);
-                }
-            }
-            search2.refine_one(window);
-            search2.solve(callback)?;
+        for (word, seed) in self.branch_order(window) {
+            self.branch(window, word, seed).solve(callback)?;
         }
         Ok(())
     }
@@ -311,31 +390,51 @@ impl Search {
         }
     }*/
 
-    pub fn solve_split(&self, overlap: &Vec<(usize, usize)>, children: &[Search; 2], callback: &mut dyn FnMut(Search) -> Result) -> Result {
+    /// Every assignment of letters to the overlap cells, in the order `solve_split` tries them.
+    pub fn overlap_values(&self, overlap: &[(usize, usize)]) -> Vec<Vec<Letter>> {
+        let mut result = vec![];
         let mut overlap_values_iter =
             CartesianProduct::new(overlap.iter().map(|&position| self.letter_set(position).unwrap().into_iter()));
         while let Some(overlap_values) = overlap_values_iter.next() {
+            result.push(overlap_values.to_vec());
+        }
+        result
+    }
+
+    pub fn restrict_overlap(&self, overlap: &[(usize, usize)], overlap_values: &[Letter]) -> Search {
+        let mut child_copy = self.clone();
+        for (&position, &value) in overlap.iter().zip(overlap_values.iter()) {
+            child_copy.retain_letter_set(position, [value].iter().cloned().collect());
+        }
+        child_copy.refine_all();
+        child_copy
+    }
+
+    pub fn combine(&self, r1: &Search, r2: &Search) -> Search {
+        let mut combination = self.clone();
+        for (window, set) in combination.sets.iter_mut() {
+            for r in &[r1, r2] {
+                if let Some(result) = r.sets.get(window) {
+                    *set = result.clone();
+                }
+            }
+            assert_eq!(set.size(), 1);
+        }
+        combination
+    }
+
+    pub fn solve_split(&self, overlap: &Vec<(usize, usize)>, children: &[Search; 2], callback: &mut dyn FnMut(Search) -> Result) -> Result {
+        for overlap_values in self.overlap_values(overlap) {
+            if let Some(monitor) = &self.monitor {
+                monitor.check()?;
+            }
             let mut options: [Option<Search>; 2] = [None, None];
             for (index, child) in children.iter().enumerate() {
-                let mut child_copy = child.clone();
-                for (&position, &value) in overlap.iter().zip(overlap_values.iter()) {
-                    child_copy.retain_letter_set(position, [value].iter().cloned().collect());
-                }
-                child_copy.refine_all();
-                let _ = child_copy.solve(&mut take_one_result(&mut options[index]));
+                let _ = child.restrict_overlap(overlap, &overlap_values).solve(&mut take_one_result(&mut options[index]));
             }
             match options {
                 [Some(r1), Some(r2)] => {
-                    let mut combination = self.clone();
-                    for (window, set) in combination.sets.iter_mut() {
-                        for r in &[&r1, &r2] {
-                            if let Some(result) = r.sets.get(window) {
-                                *set = result.clone();
-                            }
-                        }
-                        assert_eq!(set.size(), 1);
-                    }
-                    callback(combination)?;
+                    callback(self.combine(&r1, &r2))?;
                 }
                 _ => {}
             }
@@ -343,23 +442,97 @@ impl Search {
         Ok(())
     }
 
-    pub fn solve(&self, callback: &mut dyn FnMut(Search) -> Result) -> Result {
-        if self.sets.values().any(|set| set.size() == 0) {
-            return Ok(());
-        }
-        if Bernoulli::new(0.1).unwrap().sample(&mut rand::thread_rng()) {
-            //println!("{:?}", self);
-        }
+    /// The split `solve` uses, if the grid is large and falls into two loosely connected halves.
+    pub fn useful_split(&self) -> Option<(Vec<(usize, usize)>, [Search; 2])> {
         if self.sets.len() > 15 {
             if let Some((overlap, children)) = self.split_cells() {
                 if children[0].sets.len() < self.sets.len() - 2 &&
                     children[1].sets.len() < self.sets.len() - 2 && overlap.len() <= 2 {
-                    self.solve_split(&overlap, &children, callback)?;
-                    return Ok(());
+                    return Some((overlap, children));
                 }
             }
         }
+        None
+    }
+
+    pub fn solve(&self, callback: &mut dyn FnMut(Search) -> Result) -> Result {
+        if let Some(monitor) = &self.monitor {
+            monitor.visit(self)?;
+        }
+        if self.sets.values().any(|set| set.size() == 0) {
+            return Ok(());
+        }
+        if let Some((overlap, children)) = self.useful_split() {
+            self.solve_split(&overlap, &children, callback)?;
+            return Ok(());
+        }
         self.solve_direct(callback)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_score_aware_fill() {
+    use crate::fill::scored_list::scored;
+    let words = [scored("ab", 10), scored("cd", 20), scored("ac", 90), scored("bd", 90),
+        scored("ef", 80), scored("gh", 80), scored("eg", 5), scored("fh", 5)];
+    let windows = WindowMap::from_grid(&Grid::new((2, 2), |_, _| true));
+    let search = Search::new(windows, &words);
+    let mut first = None;
+    let _ = search.solve(&mut take_one_result(&mut first));
+    assert_eq!(first.unwrap().score(), 210);
+    let mut best = None;
+    let mut timed = search.clone();
+    timed.monitor = Some(Arc::new(Monitor::new(timed.sets.grid_size(), Some(Instant::now() + std::time::Duration::from_secs(60)))));
+    let _ = timed.solve(&mut take_best_result(&mut best));
+    assert_eq!(best.unwrap().score(), 210);
+    let mut strict = search.clone();
+    strict.retain_min_score(50);
+    strict.refine_all();
+    let mut result = None;
+    let _ = strict.solve(&mut take_one_result(&mut result));
+    assert!(result.is_none());
+}
+
+#[test]
+fn test_rebus() {
+    use crate::fill::scored_list::scored_words;
+    let words = scored_words(&["AB", "AC", "BON", "CON", "XY"]);
+    let rebus = Grid::new((2, 2), |x, y| if (x, y) == (1, 1) { Word::from_str("ON") } else { None });
+    let search = Search::with_rebus(WindowMap::from_grid(&Grid::new((2, 2), |_, _| true)), &words, &rebus);
+    let mut results = vec![];
+    let _ = search.solve(&mut take_all_results(&mut results));
+    let fills: Vec<String> = results.iter().map(|result| {
+        let grid = result.finish();
+        (0..2).flat_map(|y| (0..2).map(move |x| (x, y)))
+            .map(|position| match grid[position] {
+                Cell::White(Some(letter)) => letter.to_unicode(),
+                _ => '.',
+            })
+            .collect()
+    }).collect();
+    assert_eq!(fills.len(), 2);
+    assert!(fills.contains(&"ABCO".to_string()));
+    assert!(fills.contains(&"ACBO".to_string()));
+}
+
+#[test]
+fn test_seed() {
+    use crate::fill::scored_list::scored;
+    let letters = ["A", "B", "C", "D"];
+    let words: Vec<ScoredWord> = iproduct!(&letters, &letters, &letters)
+        .map(|(a, b, c)| scored(&format!("{}{}{}", a, b, c), 50))
+        .collect();
+    let search = Search::new(WindowMap::from_grid(&Grid::new((3, 3), |_, _| true)), &words);
+    let fill = |seed| {
+        let mut seeded = search.clone();
+        seeded.seed = seed;
+        let mut result = None;
+        let _ = seeded.solve(&mut take_one_result(&mut result));
+        result.unwrap().finish()
+    };
+    assert_eq!(fill(None), fill(None));
+    assert_eq!(fill(Some(1)), fill(Some(1)));
+    let fills: Vec<_> = (0..10).map(|seed| fill(Some(seed))).collect();
+    assert!(fills.iter().any(|other| *other != fills[0]));
+}
@@ -4,53 +4,91 @@ use std::fmt::{Error, Formatter};
 
 use crate::core::letter::{ALPHABET, Letter, LetterSet};
 use crate::core::word::Word;
+use crate::fill::scored_list::ScoredWord;
 use crate::util::bitset::IndexType;
 
+/// The candidates for one window, kept in descending order of score.
 #[derive(Clone)]
 pub struct WordSet {
-    words: Vec<Word>,
+    words: Vec<ScoredWord>,
     table: Vec<[usize; ALPHABET]>,
 }
 
 impl WordSet {
-    fn change_table(table: &mut Vec<[usize; ALPHABET]>, word: Word, delta: isize) {
-        for i in 0..table.len() {
-            let changed = &mut table[i][word[i].to_index()];
+    fn change_table(table: &mut Vec<[usize; ALPHABET]>, word: &[Letter], delta: isize) {
+        for (counts, letter) in table.iter_mut().zip(word.iter()) {
+            let changed = &mut counts[letter.to_index()];
             *changed = ((*changed) as isize + delta) as usize;
         }
     }
 
-    pub fn from_words(dict: &[Word], length: usize) -> Self {
+    pub fn from_words(dict: &[ScoredWord], length: usize) -> Self {
         let mut table = vec![Default::default(); length];
         let mut words = vec![];
         for &word in dict.iter() {
-            if word.len() == length {
-                Self::change_table(&mut table, word, 1);
+            if word.word.len() == length {
+                Self::change_table(&mut table, &word.word, 1);
                 words.push(word);
             }
         }
+        words.sort_by_key(|word| -(word.score as i32));
 
         WordSet {
             words: words,
             table: table,
         }
     }
+    /// Like `from_words`, for a window whose cells may hold a fixed run of letters. Words
+    /// are matched with each run spelled out, then stored with the run replaced by
+    /// `rebus_marker` so every word has one letter per cell.
+    pub fn from_rebus_words(dict: &[ScoredWord], cells: &[Option<Word>]) -> Self {
+        let length: usize = cells.iter().map(|cell| cell.map_or(1, |run| run.len())).sum();
+        let mut contracted = vec![];
+        'words: for &word in dict.iter() {
+            if word.word.len() != length {
+                continue;
+            }
+            let mut cell_word = Vec::with_capacity(cells.len());
+            let mut offset = 0;
+            for cell in cells {
+                match cell {
+                    None => {
+                        cell_word.push(word.word[offset]);
+                        offset += 1;
+                    }
+                    Some(run) => {
+                        if word.word.get(offset..offset + run.len()) != Some(&**run) {
+                            continue 'words;
+                        }
+                        cell_word.push(rebus_marker(*run));
+                        offset += run.len();
+                    }
+                }
+            }
+            contracted.push(ScoredWord { word: Word::from_letters(&cell_word), score: word.score });
+        }
+        Self::from_words(&contracted, cells.len())
+    }
     pub fn new(length: usize) -> Self {
         WordSet {
             words: vec![],
             table: vec![Default::default(); length],
         }
     }
-    pub fn add_word(&mut self, word: Word) {
-        self.words.push(word);
-        Self::change_table(&mut self.table, word, 1);
+    pub fn add_word(&mut self, word: ScoredWord) {
+        let index = self.words.iter().position(|other| other.score < word.score).unwrap_or(self.words.len());
+        self.words.insert(index, word);
+        Self::change_table(&mut self.table, &word.word, 1);
     }
     pub fn retain<F: FnMut(Word) -> bool>(&mut self, mut predicate: F) {
+        self.retain_scored(|word| predicate(word.word));
+    }
+    pub fn retain_scored<F: FnMut(ScoredWord) -> bool>(&mut self, mut predicate: F) {
         let table = &mut self.table;
         self.words.retain(|&word| {
             let retain = predicate(word);
             if !retain {
-                Self::change_table(table, word, -1);
+                Self::change_table(table, &word.word, -1);
             }
             retain
         });
@@ -68,16 +106,48 @@ impl WordSet {
     pub fn size(&self) -> usize {
         self.words.len()
     }
-    pub fn words(&self) -> &[Word] {
+    pub fn words(&self) -> &[ScoredWord] {
         &self.words
     }
+    pub fn best_score(&self) -> Option<u8> {
+        self.words.first().map(|word| word.score)
+    }
 
 }
 
+/// The letter that stands for a rebus cell in the windows through it. Both windows
+/// agree on it, so the cell never constrains either beyond its run of letters.
+pub fn rebus_marker(run: Word) -> Letter {
+    run.first().cloned().unwrap_or(Letter::from_unicode('A').unwrap())
+}
+
 impl fmt::Debug for WordSet {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{:?}", self.words)
+        write!(f, "{:?}", self.words.iter().map(|word| word.word).collect::<Vec<_>>())
     }
 }
 
+#[test]
+fn test_score_order() {
+    use crate::fill::scored_list::scored;
+    let mut set = WordSet::from_words(&[scored("ab", 10), scored("cd", 50), scored("efg", 90), scored("gh", 30)], 2);
+    assert_eq!(set.words(), &[scored("cd", 50), scored("gh", 30), scored("ab", 10)]);
+    set.add_word(scored("ij", 40));
+    set.retain_scored(|word| word.score >= 20);
+    assert_eq!(set.words(), &[scored("cd", 50), scored("ij", 40), scored("gh", 30)]);
+    assert_eq!(set.best_score(), Some(50));
+    assert_eq!(set.count(0, Letter::from_unicode('a').unwrap()), 0);
+}
+
+
 
+#[test]
+fn test_rebus_words() {
+    use crate::fill::scored_list::scored;
+    let run = Word::from_str("ON");
+    let set = WordSet::from_rebus_words(&[scored("BON", 10), scored("CON", 20), scored("BOX", 30), scored("BO", 40)], &[None, run]);
+    assert_eq!(set.words(), &[scored("CO", 20), scored("BO", 10)]);
+    assert_eq!(set.letters(1).unique(), Letter::from_unicode('O'));
+    let symbol = WordSet::from_rebus_words(&[scored("IN", 10), scored("INY", 20)], &[None, Word::from_str("&"), None]);
+    assert_eq!(symbol.words(), &[scored("IAN", 10)]);
+}
@@ -1,5 +1,5 @@
 #![allow(dead_code, non_snake_case, unused_variables, unused_imports)]
-#![feature(box_syntax, step_trait, rustc_private, const_fn)]
+#![feature(step_trait, rustc_private)]
 
 #[macro_use]
 extern crate itertools;
@@ -16,16 +16,24 @@ use std::{io, fmt, fs, iter};
 use crate::play::puzzle::{Puzzle, PuzzleCell, View, Mode};
 use std::fmt::{Display, Formatter};
 use csv::ReaderBuilder;
-use crate::core::puzzle::{Window, WindowMap, AsciiGrid, Direction, Cell};
+use crate::core::puzzle::{Window, WindowMap, AsciiGrid, Direction, Cell, MIN_LENGTH};
+use crate::core::symmetry::Symmetry;
 use crate::fill::search::{Search, Canceled, take_one_result};
 use std::collections::{HashSet, HashMap};
 use std::io::{BufRead, stdout, stdin, Write};
 use crate::play::interface::{TerminalOutput, start_rendering, stop_rendering, TerminalInput, RawScope};
-use crate::play::play::Play;
+use crate::play::play::{Play, Generator};
+use crate::play::history::History;
 use crate::play::puzzle::Mode::Editing;
 use crate::fill::dictionary::EditedDictionary;
+use crate::fill::pattern::{generate_pattern, PatternOptions, Target, ThemeEntry};
 use getopts::Options;
 use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use crate::play::raw_puzzle::PlayData;
 use std::num::ParseIntError;
 
 pub mod util;
@@ -33,65 +41,231 @@ pub mod core;
 pub mod fill;
 pub mod play;
 
-fn create(filename: &str, width: usize, height: usize) -> io::Result<()> {
-    let puzzle = Puzzle {
+fn read_puzzle(filename: &str) -> io::Result<Puzzle> {
+    let data = fs::read(filename)?;
+    match Path::new(filename).extension().and_then(|extension| extension.to_str()) {
+        Some("ipuz") => Ok(Puzzle::read_ipuz(&mut data.as_slice())?),
+        Some("jpz") => Ok(Puzzle::read_jpz(&mut data.as_slice())?),
+        Some("txt") => Ok(Puzzle::read_text(&mut data.as_slice())?),
+        _ => Ok(Puzzle::read_from(&mut data.as_slice())?),
+    }
+}
+
+fn write_puzzle(filename: &str, mut puzzle: Puzzle) -> io::Result<()> {
+    let mut data = vec![];
+    match Path::new(filename).extension().and_then(|extension| extension.to_str()) {
+        Some("ipuz") => puzzle.write_ipuz(&mut data)?,
+        Some("jpz") => puzzle.write_jpz(&mut data)?,
+        Some("txt") => puzzle.write_text(&mut data)?,
+        _ => {
+            puzzle.upgrade_version();
+            puzzle.write_to(&mut data)?
+        }
+    }
+    fs::write(filename, data)?;
+    Ok(())
+}
+
+fn new_puzzle(grid: Grid<Option<PuzzleCell>>) -> Puzzle {
+    let size = grid.size();
+    let clues = WindowMap::new(WindowMap::from_grid(
+        &Grid::new(size, |x, y| grid[(x, y)].is_some()))
+                                  .windows().zip(iter::repeat("".to_string())), size);
+    Puzzle {
         preamble: vec![],
         version: *b"1.4\0",
         title: "Title".to_string(),
         author: "Author".to_string(),
         copyright: "Copyright".to_string(),
-        grid: Grid::new((width, height), |x, y| { Some(PuzzleCell::default()) }),
-        clues: WindowMap::new(WindowMap::from_grid(
-            &Grid::new((width, height), |x, y| true))
-                                  .windows().zip(iter::repeat("".to_string())), (width, height)),
+        grid,
+        clues,
         note: "".to_string(),
+        scrambled_checksum: None,
+        unknown_sections: vec![],
+        section_order: vec![],
+        play_data: None,
+    }
+}
+
+fn create(filename: &str, width: usize, height: usize) -> io::Result<()> {
+    write_puzzle(filename, new_puzzle(Grid::new((width, height), |x, y| { Some(PuzzleCell::default()) })))
+}
+
+fn symmetry(filename: &str, symmetry: Symmetry, fix: bool) -> io::Result<()> {
+    let mut puzzle = read_puzzle(filename)?;
+    let cells = if fix {
+        puzzle.fix_symmetry(symmetry)
+    } else {
+        symmetry.asymmetric_cells(&puzzle.white())
     };
-    let mut data = vec![];
-    puzzle.write_to(&mut &mut data)?;
-    fs::write(filename, data)?;
+    for (x, y) in cells.iter() {
+        println!("{} ({}, {})", if fix { "blacked out" } else { "asymmetric" }, x, y);
+    }
+    if fix && !cells.is_empty() {
+        write_puzzle(filename, puzzle)?;
+    }
+    Ok(())
+}
+
+fn lint(filename: &str, symmetry: Symmetry) -> io::Result<()> {
+    let puzzle = read_puzzle(filename)?;
+    for finding in puzzle.lint(symmetry) {
+        println!("{}", finding.describe(&puzzle));
+    }
     Ok(())
 }
 
-fn interface(filename: &str, edit: bool) -> io::Result<()> {
+const PATTERN_TIME: Duration = Duration::from_secs(60);
+
+fn parse_theme_entry(entry: &str) -> Result<ThemeEntry, ParseError> {
+    let mut parts = entry.splitn(2, '@');
+    let word = parts.next().unwrap();
+    let word = Word::from_str(word)
+        .filter(|word| word.len() >= MIN_LENGTH)
+        .ok_or_else(|| ParseError(format!("Theme entry {} is too short", word)))?;
+    let placement = match parts.next() {
+        None => None,
+        Some(placement) => {
+            let fields: Vec<&str> = placement.split(',').collect();
+            if fields.len() != 3 {
+                return Err(ParseError(format!("Theme placement must be X,Y,a or X,Y,d")));
+            }
+            let direction = match fields[2] {
+                "a" => Direction::Across,
+                "d" => Direction::Down,
+                _ => return Err(ParseError(format!("Theme direction must be a or d"))),
+            };
+            Some(((fields[0].parse::<usize>()?, fields[1].parse::<usize>()?), direction))
+        }
+    };
+    Ok(ThemeEntry { word, placement })
+}
+
+fn pattern(filename: &str, options: &PatternOptions, word_lists: &[String]) -> Result<(), ParseError> {
+    let words = ScoredWord::load_all(word_lists)?;
+    let grid = generate_pattern(options, &words, Instant::now() + PATTERN_TIME)
+        .ok_or_else(|| ParseError(format!("No fillable pattern found")))?;
+    write_puzzle(filename, new_puzzle(Grid::new(grid.size(), |x, y| match grid[(x, y)] {
+        Cell::Black => None,
+        Cell::White(letter) => Some(PuzzleCell {
+            solution: letter.iter().map(|letter| letter.to_unicode()).collect(),
+            ..PuzzleCell::default()
+        }),
+    })))?;
+    Ok(())
+}
+
+fn print(filename: &str, output: &str, key: bool) -> io::Result<()> {
+    let puzzle = read_puzzle(filename)?;
+    let mut data = vec![];
+    match Path::new(output).extension().and_then(|extension| extension.to_str()) {
+        Some("svg") => puzzle.write_svg(&mut data, key)?,
+        Some("pdf") => puzzle.write_pdf(&mut data, key)?,
+        Some("html") => puzzle.write_html(&mut data)?,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "output must be .svg, .pdf or .html")),
+    }
+    fs::write(output, data)
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+const TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+fn tick(puzzle: &mut Puzzle, last: &mut Instant) {
+    let elapsed = last.elapsed().as_secs();
+    puzzle.advance_timer(elapsed as usize);
+    *last += Duration::from_secs(elapsed);
+}
+
+fn interface(filename: &str, edit: bool, seed: u64, symmetry: Symmetry, word_lists: &[String]) -> io::Result<()> {
+    let words = ScoredWord::load_all(word_lists)?;
     let raw = RawScope::new();
-    let data = fs::read(filename)?;
-    let mut puzzle = Puzzle::read_from(&mut data.as_slice())?;
-    let mut dictionary =
+    let mut puzzle = read_puzzle(filename)?;
+    let mut generator = Generator::new(
         EditedDictionary::new(
-            ScoredWord::default().unwrap().iter().map(|sw| sw.word).collect(),
-            "dictionaries/updates.csv");
+            words,
+            "dictionaries/updates.csv"));
+    generator.seed = seed;
     let mut view = View {
         position: (0, 0),
         direction: Direction::Across,
         mode: if edit { Mode::Editing } else { Mode::Solving },
         pencil: false,
+        symmetry,
     };
     let mut stdout = stdout();
-    let mut stdin = stdin();
     start_rendering(&mut stdout)?;
-    let mut input = TerminalInput { input: &mut stdin };
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = stdin();
+        let mut input = TerminalInput { input: &mut stdin };
+        while let Some(event) = input.read_event().transpose() {
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    if !edit {
+        puzzle.play_data.get_or_insert(PlayData { time: 0, running: true });
+    }
+    let mut history = History::new();
+    Play::new(&mut view, &mut puzzle, Some(&mut generator), None).update_candidates();
+    let mut last = Instant::now();
     loop {
+        tick(&mut puzzle, &mut last);
         let mut output = vec![];
+        let busy = generator.is_running() || generator.is_explaining() || generator.candidates_pending();
+        let progress = generator.progress();
+        let candidates = if view.mode == Mode::Editing { generator.candidates() } else { None };
+        let suggestions = match puzzle.clues.window_at(view.position, view.direction) {
+            Some(window) if view.mode == Mode::Editing => generator.suggestions(window),
+            _ => None,
+        };
+        let findings = if view.mode != Mode::Solving { Some(puzzle.lint(view.symmetry)) } else { None };
         TerminalOutput {
             output: &mut &mut output,
             view: &view,
             puzzle: &puzzle,
+            progress: progress.as_ref(),
+            conflict: generator.conflict.as_ref(),
+            candidates: candidates.as_ref(),
+            suggestions: suggestions.as_deref(),
+            findings: findings.as_deref(),
         }.render()?;
         stdout.write_all(&output)?;
-        if let Some(next) = input.read_event()? {
-            let mut play = Play::new(&mut view, &mut puzzle, Some(&mut dictionary));
+        let timing = view.mode == Mode::Solving && puzzle.play_data.as_ref().map_or(false, |data| data.running);
+        let interval = if busy {
+            Some(PROGRESS_INTERVAL)
+        } else if timing {
+            Some(TIMER_INTERVAL)
+        } else {
+            None
+        };
+        let event = if let Some(interval) = interval {
+            match events.recv_timeout(interval) {
+                Ok(event) => Some(event?),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match events.recv() {
+                Ok(event) => Some(event?),
+                Err(_) => break,
+            }
+        };
+        tick(&mut puzzle, &mut last);
+        let mut play = Play::new(&mut view, &mut puzzle, Some(&mut generator), Some(&mut history));
+        if let Some(next) = event {
             play.do_action(next);
-        } else { break; }
+        }
+        play.poll_generate();
+        play.update_candidates();
     }
     stop_rendering(&mut stdout)?;
-    let mut data = vec![];
-    puzzle.write_to(&mut &mut data)?;
-    fs::write(filename, data)?;
-    Ok(())
+    write_puzzle(filename, puzzle)
 }
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [create <FILE> <WIDTH> <HEIGHT>|edit <FILE>|play <FILE>] [options]", program);
+    let brief = format!("Usage: {} [create <FILE> <WIDTH> <HEIGHT>|edit <FILE>|play <FILE>|print <FILE> <OUTPUT>|pattern <FILE> <WIDTH> <HEIGHT>|symmetry <FILE>|lint <FILE>] [options]", program);
     print!("{}", opts.usage(&brief));
 }
 
@@ -105,6 +279,10 @@ impl<E: Display> From<E> for ParseError {
     }
 }
 
+fn symmetry_option(matches: &getopts::Matches) -> Result<Symmetry, ParseError> {
+    Ok(matches.opt_str("y").map_or(Ok(Symmetry::Rotational), |symmetry| symmetry.parse::<Symmetry>())?)
+}
+
 fn main_impl(args: &[String], matches: getopts::Matches) -> Result<(), ParseError> {
     if matches.opt_present("h") {
         return Err(ParseError(format!("")));
@@ -127,14 +305,53 @@ fn main_impl(args: &[String], matches: getopts::Matches) -> Result<(), ParseErro
                 return Err(ParseError(format!("Need 1 argument")));
             }
             let file = &matches.free[1];
-            interface(file, true)?;
+            let seed = matches.opt_str("s").map_or(Ok(0), |seed| seed.parse::<u64>())?;
+            interface(file, true, seed, symmetry_option(&matches)?, &matches.opt_strs("w"))?;
         }
         "play" => {
             if matches.free.len() != 2 {
                 return Err(ParseError(format!("Need 1 arguments")));
             }
             let file = &matches.free[1];
-            interface(file, false)?;
+            interface(file, false, 0, Symmetry::None, &matches.opt_strs("w"))?;
+        }
+        "print" => {
+            if matches.free.len() != 3 {
+                return Err(ParseError(format!("Need 2 arguments")));
+            }
+            print(&matches.free[1], &matches.free[2], matches.opt_present("k"))?;
+        }
+        "pattern" => {
+            if matches.free.len() != 4 {
+                return Err(ParseError(format!("Need 3 arguments")));
+            }
+            let target = match (matches.opt_str("b"), matches.opt_str("e")) {
+                (Some(_), Some(_)) => return Err(ParseError(format!("Give at most one of --blocks and --entries"))),
+                (Some(blocks), None) => Target::Blocks(blocks.parse::<usize>()?),
+                (None, Some(entries)) => Target::Entries(entries.parse::<usize>()?),
+                (None, None) => Target::Blocks(0),
+            };
+            let options = PatternOptions {
+                size: (matches.free[2].parse::<usize>()?, matches.free[3].parse::<usize>()?),
+                target,
+                theme: matches.opt_strs("t").iter()
+                    .map(|entry| parse_theme_entry(entry))
+                    .collect::<Result<Vec<_>, _>>()?,
+                seed: matches.opt_str("s").map_or(Ok(0), |seed| seed.parse::<u64>())?,
+            };
+            pattern(&matches.free[1], &options, &matches.opt_strs("w"))?;
+        }
+        "symmetry" => {
+            if matches.free.len() != 2 {
+                return Err(ParseError(format!("Need 1 argument")));
+            }
+            symmetry(&matches.free[1], symmetry_option(&matches)?, matches.opt_present("f"))?;
+        }
+        "lint" => {
+            if matches.free.len() != 2 {
+                return Err(ParseError(format!("Need 1 argument")));
+            }
+            lint(&matches.free[1], symmetry_option(&matches)?)?;
         }
         _ => {
             return Err(ParseError(format!("Unknown command")));
@@ -147,6 +364,14 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this help menu");
+    opts.optflag("k", "key", "print the answer key instead of a blank grid");
+    opts.optopt("s", "seed", "seed for the first generated fill or pattern", "SEED");
+    opts.optopt("y", "symmetry", "for edit, symmetry and lint, rotational (the default), left-right, up-down, diagonal or none", "SYMMETRY");
+    opts.optflag("f", "fix", "for symmetry, black out asymmetric cells instead of listing them");
+    opts.optopt("b", "blocks", "for pattern, the fewest black squares to place", "COUNT");
+    opts.optopt("e", "entries", "for pattern, the fewest entries to make", "COUNT");
+    opts.optmulti("t", "theme", "for pattern, a theme entry to place, optionally at a position", "WORD[@X,Y,a|d]");
+    opts.optmulti("w", "words", "word list to fill from (.lst, .csv, or word;score lines), highest priority first", "FILE");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m }
         Err(e) => {
@@ -0,0 +1,146 @@
+use std::collections::BTreeSet;
+
+use crate::core::puzzle::Window;
+use crate::play::puzzle::{Puzzle, PuzzleCell};
+
+const HISTORY_LIMIT: usize = 1000;
+
+/// The cells and clues one action changed, with their values before and after.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Edit {
+    cells: Vec<((usize, usize), Option<PuzzleCell>, Option<PuzzleCell>)>,
+    clues: Vec<(Window, Option<String>, Option<String>)>,
+}
+
+impl Edit {
+    fn between(before: &Puzzle, after: &Puzzle) -> Option<Self> {
+        if before.grid.size() != after.grid.size() {
+            return None;
+        }
+        let (width, height) = before.grid.size();
+        let cells: Vec<_> = iproduct!(0..height, 0..width)
+            .map(|(y, x)| (x, y))
+            .filter(|&position| before.grid[position] != after.grid[position])
+            .map(|position| (position, before.grid[position].clone(), after.grid[position].clone()))
+            .collect();
+        let windows: BTreeSet<Window> = before.clues.windows().chain(after.clues.windows()).collect();
+        let clues: Vec<_> = windows.into_iter()
+            .filter(|&window| before.clues.get(window) != after.clues.get(window))
+            .map(|window| (window, before.clues.get(window).cloned(), after.clues.get(window).cloned()))
+            .collect();
+        if cells.is_empty() && clues.is_empty() {
+            None
+        } else {
+            Some(Edit { cells, clues })
+        }
+    }
+
+    /// Moves the puzzle from one side of the edit to the other. Cells and clues that have
+    /// changed since, say by another player, are left alone.
+    fn apply(&self, puzzle: &mut Puzzle, forward: bool) {
+        let mut recolored = false;
+        for (position, old, new) in self.cells.iter() {
+            let (from, to) = if forward { (old, new) } else { (new, old) };
+            if puzzle.grid[*position] == *from {
+                recolored |= from.is_some() != to.is_some();
+                puzzle.grid[*position] = to.clone();
+            }
+        }
+        if recolored {
+            puzzle.update_windows();
+        }
+        for (window, old, new) in self.clues.iter() {
+            let (from, to) = if forward { (old, new) } else { (new, old) };
+            if let Some(to) = to {
+                let current = puzzle.clues.get(*window).cloned();
+                if current.is_some() && (current == *from || from.is_none()) {
+                    puzzle.clues[*window] = to.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks of changes to a puzzle. Each player keeps their own, so undoing
+/// only backs out that player's changes.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Records the changes from `before` to `after` as one step, if there are any, and
+    /// forgets anything that was undone.
+    pub fn record(&mut self, before: &Puzzle, after: &Puzzle) {
+        if let Some(edit) = Edit::between(before, after) {
+            self.undo.push(edit);
+            if self.undo.len() > HISTORY_LIMIT {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+    }
+    pub fn undo(&mut self, puzzle: &mut Puzzle) -> bool {
+        match self.undo.pop() {
+            Some(edit) => {
+                edit.apply(puzzle, false);
+                self.redo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn redo(&mut self, puzzle: &mut Puzzle) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                edit.apply(puzzle, true);
+                self.undo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn test_history() {
+    use crate::core::puzzle::Direction;
+    use crate::play::puzzle::test_puzzle;
+    let original = test_puzzle();
+    let mut puzzle = original.clone();
+    let mut history = History::new();
+    assert!(!history.undo(&mut puzzle));
+
+    let before = puzzle.clone();
+    puzzle.grid[(1, 1)] = Some(PuzzleCell::default());
+    puzzle.update_windows();
+    history.record(&before, &puzzle);
+    let recolored = puzzle.clone();
+    history.record(&recolored, &puzzle);
+
+    let before = puzzle.clone();
+    puzzle.clues[Window::new((0, 1), 3, Direction::Across)] = "New".to_string();
+    puzzle.grid[(0, 0)].as_mut().unwrap().solution = "Q".to_string();
+    history.record(&before, &puzzle);
+    let edited = puzzle.clone();
+
+    assert!(history.undo(&mut puzzle));
+    assert_eq!(puzzle, recolored);
+    assert!(history.undo(&mut puzzle));
+    assert_eq!(puzzle, original);
+    assert!(!history.undo(&mut puzzle));
+    assert!(history.redo(&mut puzzle));
+    assert!(history.redo(&mut puzzle));
+    assert_eq!(puzzle, edited);
+    assert!(!history.redo(&mut puzzle));
+
+    // Someone else changed the cell since, so undoing leaves it alone.
+    puzzle.grid[(0, 0)].as_mut().unwrap().solution = "Z".to_string();
+    assert!(history.undo(&mut puzzle));
+    assert_eq!(puzzle.grid[(0, 0)].as_ref().unwrap().solution, "Z");
+    assert_eq!(puzzle.clues, recolored.clues);
+}
@@ -0,0 +1,300 @@
+//! A self-contained HTML page for solving a puzzle in a browser.
+//!
+//! The page embeds the grid, clues and solution as JSON next to a small script
+//! that follows the keyboard navigation of `Play`: arrows move the cursor, space
+//! toggles the direction, tab/enter and shift-tab step through entries in
+//! `WindowMap` order, typing fills the current entry and wraps at its end, and
+//! backspace steps back and clears. Insert starts a rebus: letters pile up in the
+//! cell until Insert, Escape or Enter ends it. "Check" marks wrong letters. A
+//! scrambled puzzle is written without its solution and without "Check".
+
+use std::io;
+use std::io::Write;
+use serde_json::{Value, json};
+use crate::core::puzzle::Direction;
+use super::puzzle::Puzzle;
+
+static STYLE: &str = r#"
+body { font-family: Helvetica, Arial, sans-serif; margin: 2em; }
+#main { display: flex; flex-wrap: wrap; gap: 2em; }
+#grid { border-collapse: collapse; border: 2px solid black; }
+#grid td { width: 2em; height: 2em; border: 1px solid #888; position: relative; padding: 0;
+    text-align: center; font-size: 1.4em; cursor: pointer; user-select: none; }
+#grid td.black { background: black; cursor: default; }
+#grid td.shaded { background: #ccc; }
+#grid td.active { background: #aef; }
+#grid td.cursor { background: #fe6; }
+#grid td.circled span.letter { border: 1px solid #444; border-radius: 50%; padding: 0 0.2em; }
+#grid td.incorrect span.letter { color: #c00; }
+#grid td.rebus { outline: 2px solid #c80; }
+#grid td span.letter { display: block; overflow: hidden; white-space: nowrap; max-width: 2em; }
+#grid td small { position: absolute; top: 1px; left: 2px; font-size: 0.4em; }
+#clue { font-weight: bold; min-height: 1.5em; margin: 0.5em 0; }
+#lists { display: flex; gap: 2em; }
+#lists ol { list-style: none; padding: 0; max-width: 20em; }
+#lists li.active { background: #aef; }
+"#;
+
+static SCRIPT: &str = r#"
+const size = [PUZZLE.width, PUZZLE.height];
+const answers = PUZZLE.solution.map(row => row.map(cell => cell === null ? null : ""));
+const incorrect = PUZZLE.solution.map(row => row.map(() => false));
+let position = [0, 0];
+let direction = "across";
+let rebus = false;
+
+function windowAt(x, y, dir) {
+  return PUZZLE.windows.find(w => w.direction === dir && (dir === "across"
+    ? w.y === y && w.x <= x && x < w.x + w.length
+    : w.x === x && w.y <= y && y < w.y + w.length));
+}
+
+function cellOf(x, y) {
+  return document.getElementById("grid").rows[y].cells[x];
+}
+
+function render() {
+  const current = windowAt(position[0], position[1], direction);
+  for (let y = 0; y < size[1]; y++) {
+    for (let x = 0; x < size[0]; x++) {
+      if (PUZZLE.solution[y][x] === null) continue;
+      const td = cellOf(x, y);
+      td.querySelector("span.letter").textContent = answers[y][x];
+      td.classList.toggle("cursor", x === position[0] && y === position[1]);
+      td.classList.toggle("active", current !== undefined && windowAt(x, y, direction) === current);
+      td.classList.toggle("incorrect", incorrect[y][x]);
+      td.classList.toggle("rebus", rebus && x === position[0] && y === position[1]);
+    }
+  }
+  document.getElementById("clue").textContent =
+    current === undefined ? "" : current.number + " " + (direction === "across" ? "Across" : "Down") + ": " + current.clue;
+  PUZZLE.windows.forEach((w, i) => document.getElementById("clue" + i).classList.toggle("active", w === current));
+}
+
+function move(dx, dy) {
+  position = [Math.min(Math.max(position[0] + dx, 0), size[0] - 1), Math.min(Math.max(position[1] + dy, 0), size[1] - 1)];
+}
+
+function changeClue(delta) {
+  const current = windowAt(position[0], position[1], direction);
+  if (current === undefined) return;
+  const count = PUZZLE.windows.length;
+  const next = PUZZLE.windows[(PUZZLE.windows.indexOf(current) + delta + count) % count];
+  position = [next.x, next.y];
+  direction = next.direction;
+}
+
+function step(current, delta) {
+  const offset = direction === "across" ? position[0] - current.x : position[1] - current.y;
+  const next = (offset + delta + current.length) % current.length;
+  position = direction === "across" ? [current.x + next, current.y] : [current.x, current.y + next];
+}
+
+function type(letter) {
+  const current = windowAt(position[0], position[1], direction);
+  if (current === undefined) return;
+  if (rebus) {
+    answers[position[1]][position[0]] += letter.toUpperCase();
+  } else {
+    answers[position[1]][position[0]] = letter.toUpperCase();
+    step(current, 1);
+  }
+  incorrect[position[1]][position[0]] = false;
+}
+
+function toggleRebus() {
+  const current = windowAt(position[0], position[1], direction);
+  if (current === undefined) return;
+  if (!rebus) {
+    answers[position[1]][position[0]] = "";
+  } else if (answers[position[1]][position[0]] !== "") {
+    step(current, 1);
+  }
+  rebus = !rebus;
+}
+
+function erase() {
+  const current = windowAt(position[0], position[1], direction);
+  if (current === undefined) return;
+  if (rebus) {
+    answers[position[1]][position[0]] = answers[position[1]][position[0]].slice(0, -1);
+    return;
+  }
+  step(current, -1);
+  answers[position[1]][position[0]] = "";
+  incorrect[position[1]][position[0]] = false;
+}
+
+function check() {
+  let solved = true;
+  for (let y = 0; y < size[1]; y++) {
+    for (let x = 0; x < size[0]; x++) {
+      const solution = PUZZLE.solution[y][x];
+      if (solution === null) continue;
+      incorrect[y][x] = answers[y][x] !== "" && answers[y][x] !== solution;
+      solved = solved && answers[y][x] === solution;
+    }
+  }
+  document.getElementById("status").textContent = solved ? "Solved!" : "";
+}
+
+document.addEventListener("keydown", event => {
+  if (event.ctrlKey || event.metaKey || event.altKey) return;
+  if (rebus && (event.key === "Escape" || event.key === "Enter")) {
+    toggleRebus();
+    event.preventDefault();
+    render();
+    return;
+  }
+  switch (event.key) {
+    case "ArrowUp": move(0, -1); break;
+    case "ArrowDown": move(0, 1); break;
+    case "ArrowLeft": move(-1, 0); break;
+    case "ArrowRight": move(1, 0); break;
+    case "Tab": changeClue(event.shiftKey ? -1 : 1); break;
+    case "Enter": changeClue(1); break;
+    case " ": direction = direction === "across" ? "down" : "across"; break;
+    case "Insert": toggleRebus(); break;
+    case "Backspace": erase(); break;
+    default:
+      if (event.key.length !== 1 || !/[a-z0-9]/i.test(event.key)) return;
+      type(event.key);
+  }
+  event.preventDefault();
+  render();
+});
+
+const grid = document.getElementById("grid");
+for (let y = 0; y < size[1]; y++) {
+  const tr = grid.insertRow();
+  for (let x = 0; x < size[0]; x++) {
+    const td = tr.insertCell();
+    if (PUZZLE.solution[y][x] === null) {
+      td.className = "black";
+      continue;
+    }
+    if (PUZZLE.numbers[y][x] !== null) td.appendChild(document.createElement("small")).textContent = PUZZLE.numbers[y][x];
+    td.appendChild(document.createElement("span")).className = "letter";
+    td.classList.toggle("circled", PUZZLE.circled[y][x]);
+    td.classList.toggle("shaded", PUZZLE.shaded[y][x]);
+    td.addEventListener("click", () => {
+      if (position[0] === x && position[1] === y) {
+        direction = direction === "across" ? "down" : "across";
+      }
+      position = [x, y];
+      render();
+    });
+  }
+}
+PUZZLE.windows.forEach((w, i) => {
+  const li = document.getElementById(w.direction).appendChild(document.createElement("li"));
+  li.id = "clue" + i;
+  li.textContent = w.number + ". " + w.clue;
+  li.addEventListener("click", () => { position = [w.x, w.y]; direction = w.direction; render(); });
+});
+document.getElementById("rebus").addEventListener("click", () => { toggleRebus(); render(); });
+if (!PUZZLE.scrambled) document.getElementById("check").addEventListener("click", () => { check(); render(); });
+const first = PUZZLE.windows[0];
+if (first !== undefined) { position = [first.x, first.y]; direction = first.direction; }
+render();
+"#;
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl Puzzle {
+    pub fn to_html_data(&self) -> Value {
+        let (width, height) = self.grid.size();
+        let numbers = self.clues.numbers();
+        let rows = |cell: &dyn Fn(usize, usize) -> Value| -> Value {
+            (0..height).map(|y| (0..width).map(|x| cell(x, y)).collect::<Value>()).collect()
+        };
+        let windows: Value = self.clues.iter().map(|(window, clue)| json!({
+            "x": window.position().0,
+            "y": window.position().1,
+            "length": window.length(),
+            "direction": match window.direction() {
+                Direction::Across => "across",
+                Direction::Down => "down",
+            },
+            "number": numbers[window.position()].unwrap(),
+            "clue": clue,
+        })).collect();
+        let scrambled = self.is_scrambled();
+        json!({
+            "title": self.title,
+            "author": self.author,
+            "width": width,
+            "height": height,
+            "scrambled": scrambled,
+            "solution": rows(&|x, y| self.grid[(x, y)].as_ref().map_or(Value::Null, |cell| {
+                if scrambled { json!("") } else { json!(cell.solution) }
+            })),
+            "circled": rows(&|x, y| json!(self.grid[(x, y)].as_ref().map_or(false, |cell| cell.circled))),
+            "shaded": rows(&|x, y| json!(self.grid[(x, y)].as_ref().map_or(false, |cell| cell.shaded))),
+            "numbers": rows(&|x, y| json!(numbers[(x, y)])),
+            "windows": windows,
+        })
+    }
+
+    pub fn write_html(&self, write: &mut dyn Write) -> io::Result<()> {
+        let data = serde_json::to_string(&self.to_html_data())?.replace("</", "<\\/");
+        writeln!(write, "<!DOCTYPE html>")?;
+        writeln!(write, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(write, "<title>{}</title>", escape_html(&self.title))?;
+        writeln!(write, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+        writeln!(write, "<h1>{}</h1>", escape_html(&self.title))?;
+        writeln!(write, "<p>{}</p>", escape_html(&self.author))?;
+        writeln!(write, "<div id=\"main\">\n<div>\n<div id=\"clue\"></div>\n<table id=\"grid\"></table>")?;
+        write!(write, "<p><button id=\"rebus\">Rebus</button> ")?;
+        if !self.is_scrambled() {
+            write!(write, "<button id=\"check\">Check</button> ")?;
+        }
+        writeln!(write, "<span id=\"status\"></span></p>\n</div>")?;
+        writeln!(write, "<div id=\"lists\">")?;
+        writeln!(write, "<div><h2>Across</h2><ol id=\"across\"></ol></div>")?;
+        writeln!(write, "<div><h2>Down</h2><ol id=\"down\"></ol></div>")?;
+        writeln!(write, "</div>\n</div>")?;
+        writeln!(write, "<p><small>{}</small></p>", escape_html(&self.copyright))?;
+        writeln!(write, "<script>\nconst PUZZLE = {};{}</script>", data, SCRIPT)?;
+        writeln!(write, "</body>\n</html>")?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_html() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    let window = puzzle.clues.windows().next().unwrap();
+    puzzle.clues[window] = "</script><b>".to_string();
+    let data = puzzle.to_html_data();
+    let windows = data["windows"].as_array().unwrap();
+    assert_eq!(windows.len(), puzzle.clues.len());
+    for (window, json) in puzzle.clues.windows().zip(windows) {
+        assert_eq!(json["x"], json!(window.position().0));
+        assert_eq!(json["y"], json!(window.position().1));
+        assert_eq!(json["number"], json!(puzzle.clues.number(window)));
+    }
+    assert_eq!(data["solution"][1][1], Value::Null);
+    assert_eq!(data["solution"][0][0], json!("REB"));
+    let mut html = vec![];
+    puzzle.write_html(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert_eq!(html.matches("</script>").count(), 1);
+    assert!(html.contains("<title>Title</title>"));
+    assert!(html.contains("id=\"check\""));
+}
+
+#[test]
+fn test_html_scrambled() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    assert!(puzzle.scramble(1234));
+    let data = puzzle.to_html_data();
+    assert_eq!(data["scrambled"], json!(true));
+    assert_eq!(data["solution"][1][1], Value::Null);
+    assert_eq!(data["solution"][0][0], json!(""));
+    let mut html = vec![];
+    puzzle.write_html(&mut html).unwrap();
+    assert!(!String::from_utf8(html).unwrap().contains("id=\"check\""));
+}
@@ -4,7 +4,7 @@ use super::puzzle::PuzzleCell;
 use super::puzzle::View;
 use super::puzzle::Puzzle;
 use std::io::Read;
-use super::play::Action;
+use super::play::{Action, Scope};
 use byteorder::ReadBytesExt;
 use std::convert::TryFrom;
 use std::iter;
@@ -15,6 +15,12 @@ use termios::cfmakeraw;
 use termios::{Termios, TCSANOW, ECHO, ICANON, tcsetattr};
 use crate::play::puzzle::Mode::Solving;
 use crate::play::puzzle::Mode;
+use crate::play::raw_puzzle::PlayData;
+use crate::fill::background::Progress;
+use crate::fill::conflict::Conflict;
+use crate::fill::candidates::{Candidates, Suggestion};
+use crate::play::lint::Finding;
+use crate::core::puzzle::Cell;
 
 pub struct RawScope {
     termios: Termios
@@ -54,6 +60,12 @@ pub struct TerminalOutput<'a> {
     pub output: &'a mut dyn Write,
     pub view: &'a View,
     pub puzzle: &'a Puzzle,
+    pub progress: Option<&'a Progress>,
+    pub conflict: Option<&'a Conflict>,
+    pub candidates: Option<&'a Candidates>,
+    pub suggestions: Option<&'a [Suggestion]>,
+    /// Problems from `Puzzle::lint`, whose cells are highlighted.
+    pub findings: Option<&'a [Finding]>,
 }
 
 const CELL_WIDTH: usize = 5;
@@ -266,6 +278,14 @@ fn draw_letter(letter: u8) -> Grid<char> {
     })
 }
 
+fn format_time(seconds: usize) -> String {
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
 impl<'a> TerminalOutput<'a> {
     fn render_cell(&mut self, x: usize, y: usize, dy: usize, active_clue: Option<Window>) -> io::Result<()> {
         match &self.puzzle.grid[(x, y)] {
@@ -288,9 +308,22 @@ impl<'a> TerminalOutput<'a> {
                     };
                 write!(self.output, "\x1B[48;5;{};38;5;{}m{}\x1B[0m", background, foreground, iter::repeat(c).take(CELL_WIDTH).collect::<String>())?;
             }
-            Some(PuzzleCell { answer, solution, circled, pencil, .. }) => {
+            Some(PuzzleCell { answer, solution, circled, pencil, is_incorrect, was_incorrect, given, .. }) => {
                 let background = if self.view.position == (x, y) {
                     11
+                } else if self.conflict.map_or(false, |conflict| conflict.cells.contains(&(x, y)))
+                    || self.candidates.map_or(false, |candidates| candidates.is_stuck((x, y))) {
+                    if (x + y) % 2 == 0 {
+                        210
+                    } else {
+                        203
+                    }
+                } else if self.findings.map_or(false, |findings| findings.iter().any(|finding| finding.cells.contains(&(x, y)))) {
+                    if (x + y) % 2 == 0 {
+                        223
+                    } else {
+                        216
+                    }
                 } else if active_clue.map(|active_clue| active_clue.offset((x, y)).is_some()).unwrap_or(false) {
                     if (x + y) % 2 == 0 {
                         51
@@ -304,18 +337,34 @@ impl<'a> TerminalOutput<'a> {
                         252
                     }
                 };
-                let contents_string = if self.view.mode == Solving {
+                let mut contents_string = if self.view.mode == Solving {
                     answer
                 } else {
                     solution
                 };
+                let mut pencil = *pencil;
+                let partial_string;
+                if let Some(Progress { partial, .. }) = self.progress {
+                    if let Cell::White(Some(letter)) = partial[(x, y)] {
+                        if contents_string.is_empty() || pencil {
+                            partial_string = letter.to_unicode().to_string();
+                            contents_string = &partial_string;
+                            pencil = true;
+                        }
+                    }
+                }
+                let count = self.candidates.and_then(|candidates| candidates.letters[(x, y)]);
                 let contents = if contents_string.chars().count() == 1 {
                     let grid = draw_letter(contents_string.chars().next().unwrap() as u8);
                     (0..CELL_WIDTH).map(|dx| grid[(dx, dy)]).collect::<String>()
-                } else if contents_string == "" {
+                } else if contents_string == "" && dy == 1 && count.is_some() {
+                    pencil = true;
+                    format!("{:^width$}", count.unwrap(), width = CELL_WIDTH)
+                } else if contents_string == "" || dy != 1 {
                     iter::repeat(' ').take(CELL_WIDTH).collect::<String>()
                 } else {
-                    unimplemented!();
+                    let rebus: String = contents_string.chars().take(CELL_WIDTH).collect();
+                    format!("{:^width$}", rebus, width = CELL_WIDTH)
                 };
                 let code = if *circled {
                     format!("\x1b[4m{}\x1b[0m", contents)
@@ -323,12 +372,42 @@ impl<'a> TerminalOutput<'a> {
                 } else {
                     format!("{}", contents)
                 };
-                let foreground = if *pencil { 244 } else { 16 };
+                let foreground = if self.view.mode != Solving {
+                    if pencil { 244 } else { 16 }
+                } else if *is_incorrect {
+                    196
+                } else if *given {
+                    27
+                } else if *was_incorrect {
+                    166
+                } else if pencil {
+                    244
+                } else {
+                    16
+                };
                 write!(self.output, "\x1B[48;5;{};38;5;{}m{}\x1B[0m", background, foreground, code)?;
             }
         }
         Ok(())
     }
+    /// One line of the panel listing words for the current window, beside the grid.
+    fn render_suggestion(&mut self, line: usize) -> io::Result<()> {
+        let suggestions = match self.suggestions {
+            Some(suggestions) => suggestions,
+            None => return Ok(()),
+        };
+        if line == 0 {
+            write!(self.output, "  Alt+digit: word   score crossing")?;
+        } else if let Some(suggestion) = suggestions.get(line - 1) {
+            write!(self.output, "  {}. {:<15} {:>3}", line % 10, suggestion.word.word.to_unicode(), suggestion.word.score)?;
+            match suggestion.crossings {
+                Some(0) => write!(self.output, " \x1B[38;5;196m{:>8}\x1B[0m", 0)?,
+                Some(crossings) => write!(self.output, " {:>8}", crossings)?,
+                None => write!(self.output, " {:>8}", "-")?,
+            }
+        }
+        Ok(())
+    }
     pub fn render(&mut self) -> io::Result<()> {
         write!(self.output, "\x1b]0;{}\x07", self.puzzle.title)?;
         write!(self.output, "\x1B[H\x1B[J")?;
@@ -341,6 +420,7 @@ impl<'a> TerminalOutput<'a> {
                 for x in 0..self.puzzle.grid.size().0 {
                     self.render_cell(x, y, dy, active_clue)?;
                 }
+                self.render_suggestion(y * CELL_HEIGHT + dy)?;
                 write!(self.output, "\r\n")?;
             }
         }
@@ -353,6 +433,40 @@ impl<'a> TerminalOutput<'a> {
             if self.view.pencil {
                 write!(self.output, "\x1B[0m")?;
             }
+            if let Some(progress) = self.progress {
+                write!(self.output, " filling: {} nodes, {}/{} words (any key stops)",
+                       progress.nodes, progress.deepest, self.puzzle.clues.len())?;
+            }
+            if let (Some(candidates), Some(active_clue)) = (self.candidates, active_clue) {
+                write!(self.output, " {} words", candidates.words.get(active_clue).cloned().unwrap_or(0))?;
+                let crossing = self.puzzle.clues.window_at(self.view.position, self.view.direction.perpendicular());
+                if let Some(count) = crossing.and_then(|crossing| candidates.words.get(crossing)) {
+                    write!(self.output, ", {} crossing", count)?;
+                }
+            }
+            if self.view.mode != Mode::Solving {
+                write!(self.output, " symmetry: {}", self.view.symmetry)?;
+                let asymmetric = self.view.symmetry.asymmetric_cells(&self.puzzle.white()).len();
+                if asymmetric > 0 {
+                    write!(self.output, " ({} asymmetric)", asymmetric)?;
+                }
+            }
+            if let Some(findings) = self.findings {
+                if !findings.is_empty() {
+                    write!(self.output, " {} problems", findings.len())?;
+                }
+                if let Some(finding) = findings.iter().find(|finding| finding.is_about(active_clue, self.view.position)) {
+                    write!(self.output, " ({})", finding.describe(self.puzzle))?;
+                }
+            }
+            if let Some(conflict) = self.conflict {
+                write!(self.output, " no fill: {} conflicting entries", conflict.windows.len())?;
+            }
+            if self.view.mode == Mode::Solving {
+                if let Some(PlayData { time, running }) = &self.puzzle.play_data {
+                    write!(self.output, " {} {}", if *running { '⏱' } else { '⏸' }, format_time(*time))?;
+                }
+            }
             write!(self.output, "\r\n")?;
         }
 
@@ -433,6 +547,30 @@ impl<'a> TerminalInput<'a> {
                         }
                         z => {}
                     }
+                    b'z' => {
+                        return Ok(Some(Action::Redo));
+                    }
+                    b'l' => {
+                        return Ok(Some(Action::Check { scope: Scope::Letter }));
+                    }
+                    b'w' => {
+                        return Ok(Some(Action::Check { scope: Scope::Word }));
+                    }
+                    b'p' => {
+                        return Ok(Some(Action::Check { scope: Scope::Puzzle }));
+                    }
+                    b'L' => {
+                        return Ok(Some(Action::Reveal { scope: Scope::Letter }));
+                    }
+                    b'W' => {
+                        return Ok(Some(Action::Reveal { scope: Scope::Word }));
+                    }
+                    b'P' => {
+                        return Ok(Some(Action::Reveal { scope: Scope::Puzzle }));
+                    }
+                    digit @ b'0'..=b'9' => {
+                        return Ok(Some(Action::ChooseSuggestion { index: (digit - b'0' + 9) as usize % 10 }));
+                    }
                     y => {}
                 }
                 //x @ b'A'..=b'Z' | x @ b'a'..=b'z' | b' ' => {
@@ -445,9 +583,21 @@ impl<'a> TerminalInput<'a> {
                 23 => {
                     return Ok(Some(Action::ChangeColor));
                 }
+                25 => {
+                    return Ok(Some(Action::CycleSymmetry));
+                }
+                6 => {
+                    return Ok(Some(Action::FixSymmetry));
+                }
                 7 => {
                     return Ok(Some(Action::Generate));
                 }
+                14 => {
+                    return Ok(Some(Action::GenerateAnother));
+                }
+                2 => {
+                    return Ok(Some(Action::GenerateBest));
+                }
                 1 => {
                     return Ok(Some(Action::Reject));
                 }
@@ -463,6 +613,15 @@ impl<'a> TerminalInput<'a> {
                 5 => {
                     return Ok(Some(Action::ToggleEditClue));
                 }
+                20 => {
+                    return Ok(Some(Action::PauseTimer));
+                }
+                18 => {
+                    return Ok(Some(Action::ResumeTimer));
+                }
+                26 => {
+                    return Ok(Some(Action::Undo));
+                }
                 x => {
                     eprintln!("unknown = {}", x);
                 }
@@ -471,6 +630,13 @@ impl<'a> TerminalInput<'a> {
     }
 }
 
+#[test]
+fn test_format_time() {
+    assert_eq!(format_time(0), "0:00");
+    assert_eq!(format_time(75), "1:15");
+    assert_eq!(format_time(3725), "1:02:05");
+}
+
 #[test]
 fn test_letter() {
     for letter in b'A'..=b'Z' {
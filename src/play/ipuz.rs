@@ -0,0 +1,320 @@
+//! Reading and writing crosswords in the ipuz format (http://ipuz.org).
+//!
+//! Only the rectangular crossword subset maps onto `Puzzle`. These ipuz fields are
+//! ignored when reading and never written:
+//! - `origin`, `publisher`, `publication`, `url`, `uniqueid`, `intro`, `explanation`,
+//!   `annotation`, `editor`, `date`, `difficulty`, `checksum` and `answer`/`answers`
+//! - `styles` and every cell style other than `shapebg: circle` and `highlight`
+//!   (read as shaded, as is any `color`), including bars (`barred`)
+//! - omitted (`null`) cells in `puzzle`, which are read as blocks
+//! - clue `enumeration`, `references`, `continued` and `highlight`, and clue
+//!   directions other than `Across` and `Down`
+//! - the `pencil`, `was_incorrect` and `is_incorrect` flags of `PuzzleCell` have no
+//!   ipuz equivalent and are dropped when writing.
+
+use std::{fmt, error, io};
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use serde_json::{Value, Map, json};
+use crate::util::grid::Grid;
+use crate::core::puzzle::{Direction, WindowMap};
+use super::puzzle::{Puzzle, PuzzleCell};
+
+#[derive(Debug)]
+pub enum IpuzError {
+    Json(serde_json::Error),
+    MissingField(&'static str),
+    InvalidField(&'static str),
+    UnknownClue {
+        direction: Direction,
+        number: String,
+    },
+}
+
+impl fmt::Display for IpuzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpuzError::Json(error) => write!(f, "{}", error),
+            IpuzError::MissingField(field) => write!(f, "missing field {:?}", field),
+            IpuzError::InvalidField(field) => write!(f, "invalid field {:?}", field),
+            IpuzError::UnknownClue { direction, number } =>
+                write!(f, "no {:?} entry numbered {}", direction, number),
+        }
+    }
+}
+
+impl error::Error for IpuzError {}
+
+impl From<serde_json::Error> for IpuzError {
+    fn from(error: serde_json::Error) -> Self {
+        IpuzError::Json(error)
+    }
+}
+
+impl From<IpuzError> for io::Error {
+    fn from(error: IpuzError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+fn field<'a>(object: &'a Value, name: &'static str) -> Result<&'a Value, IpuzError> {
+    object.get(name).ok_or(IpuzError::MissingField(name))
+}
+
+fn string_field(object: &Value, name: &'static str) -> Result<String, IpuzError> {
+    match object.get(name) {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(IpuzError::InvalidField(name)),
+    }
+}
+
+fn label(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(string) => Some(string.clone()),
+        _ => None,
+    }
+}
+
+fn grid_field<'a>(object: &'a Value, name: &'static str, size: (usize, usize)) -> Result<Option<Grid<&'a Value>>, IpuzError> {
+    let rows = match object.get(name) {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Array(rows)) => rows,
+        Some(_) => return Err(IpuzError::InvalidField(name)),
+    };
+    if rows.len() != size.1 || rows.iter().any(|row| row.as_array().map(|row| row.len()) != Some(size.0)) {
+        return Err(IpuzError::InvalidField(name));
+    }
+    Ok(Some(Grid::new(size, |x, y| &rows[y][x])))
+}
+
+impl Puzzle {
+    pub fn from_ipuz(ipuz: &Value) -> Result<Puzzle, IpuzError> {
+        let dimensions = field(ipuz, "dimensions")?;
+        let size = (
+            field(dimensions, "width")?.as_u64().ok_or(IpuzError::InvalidField("width"))? as usize,
+            field(dimensions, "height")?.as_u64().ok_or(IpuzError::InvalidField("height"))? as usize);
+        let block = ipuz.get("block").and_then(label).unwrap_or("#".to_string());
+        let empty = ipuz.get("empty").and_then(label).unwrap_or("0".to_string());
+        let is_block = |value: &Value| value.is_null() || label(value).as_ref() == Some(&block);
+        let letters = |value: &Value| match value {
+            Value::Object(object) => object.get("value").and_then(label),
+            value => label(value),
+        }.filter(|value| value != &empty && value != &block).unwrap_or(String::new());
+
+        let puzzle = grid_field(ipuz, "puzzle", size)?.ok_or(IpuzError::MissingField("puzzle"))?;
+        let solution = grid_field(ipuz, "solution", size)?;
+        let saved = grid_field(ipuz, "saved", size)?;
+        let mut labels = HashMap::new();
+        let grid = Grid::new(size, |x, y| {
+            let cell = puzzle[(x, y)];
+            let number = match cell {
+                Value::Object(object) => object.get("cell"),
+                cell => Some(cell),
+            };
+            if number.map_or(true, is_block) {
+                return None;
+            }
+            if let Some(number) = number.and_then(label) {
+                labels.insert(number, (x, y));
+            }
+            let given = match cell {
+                Value::Object(object) => object.get("value").map_or(String::new(), letters),
+                _ => String::new(),
+            };
+            let style = cell.get("style");
+            let circled = style
+                .and_then(|style| style.get("shapebg"))
+                .and_then(|shape| shape.as_str()) == Some("circle");
+            let shaded = style.map_or(false, |style| {
+                style.get("highlight").and_then(|highlight| highlight.as_bool()) == Some(true)
+                    || style.get("color").map_or(false, |color| !color.is_null())
+            });
+            Some(PuzzleCell {
+                solution: solution.as_ref().map_or(String::new(), |solution| letters(solution[(x, y)])),
+                answer: if given.is_empty() {
+                    saved.as_ref().map_or(String::new(), |saved| letters(saved[(x, y)]))
+                } else {
+                    given.clone()
+                },
+                given: !given.is_empty(),
+                circled,
+                shaded,
+                ..PuzzleCell::default()
+            })
+        });
+
+        let windows = WindowMap::from_grid(&Grid::new(size, |x, y| grid[(x, y)].is_some()));
+        let mut clues = WindowMap::new(windows.windows().map(|window| (window, String::new())), size);
+        if let Some(lists) = ipuz.get("clues") {
+            let lists = lists.as_object().ok_or(IpuzError::InvalidField("clues"))?;
+            for (name, list) in lists {
+                let direction = match name.split(':').next().unwrap() {
+                    "Across" => Direction::Across,
+                    "Down" => Direction::Down,
+                    _ => continue,
+                };
+                for clue in list.as_array().ok_or(IpuzError::InvalidField("clues"))? {
+                    let (number, text) = match clue {
+                        Value::Array(pair) if pair.len() == 2 => (label(&pair[0]), pair[1].as_str()),
+                        Value::Object(object) => (
+                            object.get("number").and_then(label),
+                            object.get("clue").and_then(|clue| clue.as_str())),
+                        _ => (None, None),
+                    };
+                    let (number, text) = match (number, text) {
+                        (Some(number), Some(text)) => (number, text),
+                        _ => return Err(IpuzError::InvalidField("clues")),
+                    };
+                    let window = labels.get(&number)
+                        .and_then(|&position| clues.window_at(position, direction))
+                        .filter(|window| labels.get(&number) == Some(&window.position()))
+                        .ok_or(IpuzError::UnknownClue { direction, number: number.clone() })?;
+                    clues[window] = text.to_string();
+                }
+            }
+        }
+
+        Ok(Puzzle {
+            preamble: vec![],
+            version: *b"1.4\0",
+            title: string_field(ipuz, "title")?,
+            author: string_field(ipuz, "author")?,
+            copyright: string_field(ipuz, "copyright")?,
+            grid,
+            clues,
+            note: string_field(ipuz, "notes")?,
+            scrambled_checksum: None,
+            unknown_sections: vec![],
+            section_order: vec![],
+            play_data: None,
+        })
+    }
+
+    pub fn to_ipuz(&self) -> Value {
+        let (width, height) = self.grid.size();
+        let numbers = self.clues.numbers();
+        let rows = |cell: &dyn Fn((usize, usize), &PuzzleCell) -> Value| -> Value {
+            (0..height).map(|y| (0..width).map(|x| match &self.grid[(x, y)] {
+                None => json!("#"),
+                Some(puzzle_cell) => cell((x, y), puzzle_cell),
+            }).collect::<Value>()).collect()
+        };
+        let letters = |string: &String| if string.is_empty() { json!(0) } else { json!(string) };
+        let puzzle = rows(&|position, cell| {
+            let number = numbers[position].map_or(json!(0), |number| json!(number));
+            if !cell.circled && !cell.shaded && !cell.given {
+                return number;
+            }
+            let mut object = Map::new();
+            object.insert("cell".to_string(), number);
+            if cell.circled || cell.shaded {
+                let mut style = Map::new();
+                if cell.circled {
+                    style.insert("shapebg".to_string(), json!("circle"));
+                }
+                if cell.shaded {
+                    style.insert("highlight".to_string(), json!(true));
+                }
+                object.insert("style".to_string(), Value::Object(style));
+            }
+            if cell.given {
+                object.insert("value".to_string(), json!(cell.answer));
+            }
+            Value::Object(object)
+        });
+        let solution = rows(&|_, cell| letters(&cell.solution));
+        let saved = rows(&|_, cell| letters(&cell.answer));
+        let clues = |direction: Direction| -> Value {
+            self.clues.iter()
+                .filter(|(window, _)| window.direction() == direction)
+                .map(|(window, clue)| json!([numbers[window.position()].unwrap(), clue]))
+                .collect()
+        };
+        json!({
+            "version": "http://ipuz.org/v2",
+            "kind": ["http://ipuz.org/crossword#1"],
+            "title": self.title,
+            "author": self.author,
+            "copyright": self.copyright,
+            "notes": self.note,
+            "dimensions": {"width": width, "height": height},
+            "block": "#",
+            "empty": 0,
+            "puzzle": puzzle,
+            "solution": solution,
+            "saved": saved,
+            "clues": {
+                "Across": clues(Direction::Across),
+                "Down": clues(Direction::Down),
+            },
+        })
+    }
+
+    pub fn read_ipuz(read: &mut dyn Read) -> Result<Puzzle, IpuzError> {
+        Self::from_ipuz(&serde_json::from_reader(read)?)
+    }
+
+    /// Fails for a scrambled puzzle, as ipuz has no way to mark the solution as scrambled.
+    pub fn write_ipuz(&self, write: &mut dyn Write) -> io::Result<()> {
+        if self.is_scrambled() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot write a scrambled puzzle as ipuz"));
+        }
+        serde_json::to_writer_pretty(&mut *write, &self.to_ipuz())?;
+        writeln!(write)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_round_trip_read_write() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    if let Some(cell) = puzzle.grid[(1, 0)].as_mut() {
+        cell.given = true;
+        cell.answer = "X".to_string();
+    }
+    if let Some(cell) = puzzle.grid[(0, 1)].as_mut() {
+        cell.shaded = true;
+    }
+    if let Some(cell) = puzzle.grid[(2, 1)].as_mut() {
+        cell.solution = "".to_string();
+        cell.answer = "".to_string();
+    }
+    let mut data = vec![];
+    puzzle.write_ipuz(&mut data).unwrap();
+    let puzzle2 = Puzzle::read_ipuz(&mut data.as_slice()).unwrap();
+    assert_eq!(puzzle, puzzle2);
+    assert!(puzzle.scramble(1234));
+    assert!(puzzle.write_ipuz(&mut vec![]).is_err());
+}
+
+#[test]
+fn test_read() {
+    let ipuz = json!({
+        "version": "http://ipuz.org/v2",
+        "kind": ["http://ipuz.org/crossword#1"],
+        "dimensions": {"width": 3, "height": 2},
+        "title": "Tiny",
+        "puzzle": [[1, {"cell": 2, "style": {"shapebg": "circle"}}, "#"], [3, 0, null]],
+        "solution": [["A", "B", "#"], ["C", "DE", null]],
+        "clues": {
+            "Across": [[1, "First"], {"number": 3, "clue": "Second"}],
+            "Down:Down": [["1", "Third"], [2, "Fourth"]],
+        },
+    });
+    let puzzle = Puzzle::from_ipuz(&ipuz).unwrap();
+    assert_eq!(puzzle.title, "Tiny");
+    assert_eq!(puzzle.grid[(1, 1)].as_ref().unwrap().solution, "DE");
+    assert!(puzzle.grid[(1, 0)].as_ref().unwrap().circled);
+    assert!(puzzle.grid[(2, 1)].is_none());
+    assert_eq!(puzzle.clues.values().cloned().collect::<Vec<_>>(), vec!["First", "Second", "Third", "Fourth"]);
+    match Puzzle::from_ipuz(&json!({
+        "dimensions": {"width": 1, "height": 2},
+        "puzzle": [[1], [0]],
+        "clues": {"Across": [[1, "Nope"]]},
+    })) {
+        Err(IpuzError::UnknownClue { direction: Direction::Across, .. }) => {}
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,378 @@
+//! Reading and writing Crossword Compiler (.jpz) puzzles.
+//!
+//! Only rectangular grids are supported. Cell solutions (including rebus solutions),
+//! blocks, circles (`background-shape="circle"`), shading (any non-white `background-color`),
+//! user answers (`solve-state`), revealed cells (`hint`), clue lists with word
+//! references and the title, creator, copyright and description metadata are kept.
+//! Everything else, including bars, the actual colors, hidden and void cells,
+//! multiple clue formatting and applet settings, is dropped.
+
+use std::{fmt, error, io};
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use xml::reader::{EventReader, XmlEvent as ReadEvent};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent as WriteEvent};
+use crate::util::grid::Grid;
+use crate::core::puzzle::{Direction, Window, WindowMap};
+use super::puzzle::{Puzzle, PuzzleCell};
+
+static APPLET_NAMESPACE: &str = "http://crossword.info/xml/crossword-compiler-applet";
+static PUZZLE_NAMESPACE: &str = "http://crossword.info/xml/rectangular-puzzle";
+
+#[derive(Debug)]
+pub enum JpzError {
+    Xml(xml::reader::Error),
+    MissingElement(&'static str),
+    InvalidAttribute {
+        element: &'static str,
+        attribute: &'static str,
+    },
+    UnknownWord(String),
+}
+
+impl fmt::Display for JpzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JpzError::Xml(error) => write!(f, "{}", error),
+            JpzError::MissingElement(element) => write!(f, "missing <{}>", element),
+            JpzError::InvalidAttribute { element, attribute } =>
+                write!(f, "missing or invalid {:?} on <{}>", attribute, element),
+            JpzError::UnknownWord(word) => write!(f, "word {:?} is not an entry in the grid", word),
+        }
+    }
+}
+
+impl error::Error for JpzError {}
+
+impl From<xml::reader::Error> for JpzError {
+    fn from(error: xml::reader::Error) -> Self {
+        JpzError::Xml(error)
+    }
+}
+
+impl From<JpzError> for io::Error {
+    fn from(error: JpzError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn parse(read: &mut dyn Read) -> Result<Element, JpzError> {
+        let mut stack = vec![Element::default()];
+        for event in EventReader::new(read) {
+            match event? {
+                ReadEvent::StartElement { name, attributes, .. } => {
+                    stack.push(Element {
+                        name: name.local_name,
+                        attributes: attributes.into_iter()
+                            .map(|attribute| (attribute.name.local_name, attribute.value))
+                            .collect(),
+                        ..Element::default()
+                    });
+                }
+                ReadEvent::EndElement { .. } => {
+                    let element = stack.pop().unwrap();
+                    let parent = stack.last_mut().unwrap();
+                    parent.text.push_str(&element.text);
+                    parent.children.push(element);
+                }
+                ReadEvent::Characters(text) | ReadEvent::CData(text) => {
+                    stack.last_mut().unwrap().text.push_str(&text);
+                }
+                _ => {}
+            }
+        }
+        Ok(stack.pop().unwrap())
+    }
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item=&'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+    fn child(&self, name: &'static str) -> Result<&Element, JpzError> {
+        self.children(name).next().ok_or(JpzError::MissingElement(name))
+    }
+    fn find(&self, name: &'static str) -> Result<&Element, JpzError> {
+        if self.name == name {
+            return Ok(self);
+        }
+        self.children.iter()
+            .filter_map(|child| child.find(name).ok())
+            .next()
+            .ok_or(JpzError::MissingElement(name))
+    }
+    fn text_of(&self, name: &'static str) -> String {
+        self.child(name).map_or(String::new(), |child| child.text.trim().to_string())
+    }
+    fn attribute(&self, element: &'static str, attribute: &'static str) -> Result<&str, JpzError> {
+        self.attributes.get(attribute)
+            .map(|value| value.as_str())
+            .ok_or(JpzError::InvalidAttribute { element, attribute })
+    }
+    fn coordinate(&self, element: &'static str, attribute: &'static str) -> Result<usize, JpzError> {
+        self.attribute(element, attribute)?
+            .parse::<usize>().ok()
+            .and_then(|value| value.checked_sub(1))
+            .ok_or(JpzError::InvalidAttribute { element, attribute })
+    }
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let mut bounds = range.splitn(2, '-').map(|bound| bound.trim().parse::<usize>().ok()?.checked_sub(1));
+    let start = bounds.next()??;
+    let end = bounds.next().map_or(Some(start), |end| end)?;
+    Some((start, end))
+}
+
+fn word_window(word: &Element, clues: &WindowMap<String>) -> Result<Window, JpzError> {
+    let id = word.attribute("word", "id").unwrap_or("").to_string();
+    let unknown = || JpzError::UnknownWord(id.clone());
+    let mut positions = vec![];
+    if let (Some(xs), Some(ys)) = (word.attributes.get("x"), word.attributes.get("y")) {
+        let (x0, x1) = parse_range(xs).ok_or_else(unknown)?;
+        let (y0, y1) = parse_range(ys).ok_or_else(unknown)?;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                positions.push((x, y));
+            }
+        }
+    }
+    for cells in word.children("cells") {
+        positions.push((cells.coordinate("cells", "x")?, cells.coordinate("cells", "y")?));
+    }
+    if positions.len() < 2 {
+        return Err(unknown());
+    }
+    let direction = if positions.iter().all(|position| position.1 == positions[0].1) {
+        Direction::Across
+    } else {
+        Direction::Down
+    };
+    let (width, height) = clues.grid_size();
+    if positions[0].0 >= width || positions[0].1 >= height {
+        return Err(unknown());
+    }
+    clues.window_at(positions[0], direction)
+        .filter(|window| window.position() == positions[0] && window.length() == positions.len())
+        .ok_or_else(unknown)
+}
+
+impl Puzzle {
+    pub fn read_jpz(read: &mut dyn Read) -> Result<Puzzle, JpzError> {
+        let root = Element::parse(read)?;
+        let puzzle = root.find("rectangular-puzzle")?;
+        let crossword = puzzle.child("crossword")?;
+        let grid_element = crossword.child("grid")?;
+        let size = (grid_element.coordinate("grid", "width")? + 1, grid_element.coordinate("grid", "height")? + 1);
+        let mut cells = HashMap::new();
+        for cell in grid_element.children("cell") {
+            let position = (cell.coordinate("cell", "x")?, cell.coordinate("cell", "y")?);
+            if position.0 >= size.0 || position.1 >= size.1 {
+                return Err(JpzError::InvalidAttribute { element: "cell", attribute: "x" });
+            }
+            cells.insert(position, cell);
+        }
+        let grid = Grid::new(size, |x, y| {
+            let cell = cells.get(&(x, y))?;
+            if cell.attributes.get("type").map_or(false, |kind| kind == "block" || kind == "void") {
+                return None;
+            }
+            let attribute = |name: &str| cell.attributes.get(name).cloned().unwrap_or(String::new());
+            Some(PuzzleCell {
+                solution: attribute("solution"),
+                answer: attribute("solve-state"),
+                given: attribute("hint") == "true",
+                circled: attribute("background-shape") == "circle",
+                shaded: !["", "#FFFFFF"].contains(&attribute("background-color").to_uppercase().as_str()),
+                ..PuzzleCell::default()
+            })
+        });
+
+        let windows = WindowMap::from_grid(&Grid::new(size, |x, y| grid[(x, y)].is_some()));
+        let mut clues = WindowMap::new(windows.windows().map(|window| (window, String::new())), size);
+        let mut words = HashMap::new();
+        for word in crossword.children("word") {
+            words.insert(word.attribute("word", "id")?, word_window(word, &clues)?);
+        }
+        for list in crossword.children("clues") {
+            for clue in list.children("clue") {
+                let id = clue.attribute("clue", "word")?;
+                let window = *words.get(id).ok_or(JpzError::UnknownWord(id.to_string()))?;
+                clues[window] = clue.text.trim().to_string();
+            }
+        }
+
+        let metadata = puzzle.child("metadata").ok();
+        let metadata = |name: &'static str| metadata.map_or(String::new(), |metadata| metadata.text_of(name));
+        Ok(Puzzle {
+            preamble: vec![],
+            version: *b"1.4\0",
+            title: metadata("title"),
+            author: metadata("creator"),
+            copyright: metadata("copyright"),
+            grid,
+            clues,
+            note: metadata("description"),
+            scrambled_checksum: None,
+            unknown_sections: vec![],
+            section_order: vec![],
+            play_data: None,
+        })
+    }
+
+    /// Fails for a scrambled puzzle, as jpz has no way to mark the solution as scrambled.
+    pub fn write_jpz(&self, write: &mut dyn Write) -> io::Result<()> {
+        if self.is_scrambled() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot write a scrambled puzzle as jpz"));
+        }
+        let mut writer = EmitterConfig::new().perform_indent(true).create_writer(write);
+        self.write_jpz_events(&mut writer).map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+    }
+
+    fn write_jpz_events(&self, writer: &mut EventWriter<&mut dyn Write>) -> xml::writer::Result<()> {
+        fn text_element(writer: &mut EventWriter<&mut dyn Write>, name: &str, text: &str) -> xml::writer::Result<()> {
+            writer.write(WriteEvent::start_element(name))?;
+            writer.write(WriteEvent::characters(text))?;
+            writer.write(WriteEvent::end_element())
+        }
+        let (width, height) = self.grid.size();
+        let numbers = self.clues.numbers();
+        writer.write(WriteEvent::start_element("crossword-compiler-applet").default_ns(APPLET_NAMESPACE))?;
+        writer.write(WriteEvent::start_element("rectangular-puzzle")
+            .default_ns(PUZZLE_NAMESPACE)
+            .attr("alphabet", "ABCDEFGHIJKLMNOPQRSTUVWXYZ"))?;
+        writer.write(WriteEvent::start_element("metadata"))?;
+        text_element(writer, "title", &self.title)?;
+        text_element(writer, "creator", &self.author)?;
+        text_element(writer, "copyright", &self.copyright)?;
+        text_element(writer, "description", &self.note)?;
+        writer.write(WriteEvent::end_element())?;
+
+        writer.write(WriteEvent::start_element("crossword"))?;
+        let (width_string, height_string) = (width.to_string(), height.to_string());
+        writer.write(WriteEvent::start_element("grid")
+            .attr("width", &width_string)
+            .attr("height", &height_string))?;
+        writer.write(WriteEvent::start_element("grid-look").attr("numbering-scheme", "normal"))?;
+        writer.write(WriteEvent::end_element())?;
+        for y in 0..height {
+            for x in 0..width {
+                let (x_string, y_string) = ((x + 1).to_string(), (y + 1).to_string());
+                let number = numbers[(x, y)].map(|number| number.to_string());
+                let mut element = WriteEvent::start_element("cell").attr("x", &x_string).attr("y", &y_string);
+                match &self.grid[(x, y)] {
+                    None => element = element.attr("type", "block"),
+                    Some(cell) => {
+                        element = element.attr("solution", &cell.solution);
+                        if let Some(number) = &number {
+                            element = element.attr("number", number);
+                        }
+                        if !cell.answer.is_empty() {
+                            element = element.attr("solve-state", &cell.answer);
+                        }
+                        if cell.given {
+                            element = element.attr("hint", "true");
+                        }
+                        if cell.circled {
+                            element = element.attr("background-shape", "circle");
+                        }
+                        if cell.shaded {
+                            element = element.attr("background-color", "#C0C0C0");
+                        }
+                    }
+                }
+                writer.write(element)?;
+                writer.write(WriteEvent::end_element())?;
+            }
+        }
+        writer.write(WriteEvent::end_element())?;
+
+        let ids: HashMap<Window, String> =
+            self.clues.windows().enumerate().map(|(index, window)| (window, (index + 1).to_string())).collect();
+        for window in self.clues.windows() {
+            let (x, y) = window.position();
+            let (end_x, end_y) = window.position_at(window.length() - 1);
+            let xs = if x == end_x { (x + 1).to_string() } else { format!("{}-{}", x + 1, end_x + 1) };
+            let ys = if y == end_y { (y + 1).to_string() } else { format!("{}-{}", y + 1, end_y + 1) };
+            writer.write(WriteEvent::start_element("word").attr("id", &ids[&window]).attr("x", &xs).attr("y", &ys))?;
+            writer.write(WriteEvent::end_element())?;
+        }
+        for &(direction, title) in &[(Direction::Across, "Across"), (Direction::Down, "Down")] {
+            writer.write(WriteEvent::start_element("clues").attr("ordering", "normal"))?;
+            writer.write(WriteEvent::start_element("title"))?;
+            text_element(writer, "b", title)?;
+            writer.write(WriteEvent::end_element())?;
+            for (window, clue) in self.clues.iter().filter(|(window, _)| window.direction() == direction) {
+                let number = numbers[window.position()].unwrap().to_string();
+                writer.write(WriteEvent::start_element("clue").attr("word", &ids[&window]).attr("number", &number))?;
+                writer.write(WriteEvent::characters(clue))?;
+                writer.write(WriteEvent::end_element())?;
+            }
+            writer.write(WriteEvent::end_element())?;
+        }
+        writer.write(WriteEvent::end_element())?;
+        writer.write(WriteEvent::end_element())?;
+        writer.write(WriteEvent::end_element())?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_round_trip_read_write() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    if let Some(cell) = puzzle.grid[(1, 0)].as_mut() {
+        cell.given = true;
+        cell.shaded = true;
+    }
+    puzzle.clues[Window::new((0, 2), 3, Direction::Across)] = "<Tricky> & \"quoted\"".to_string();
+    let mut data = vec![];
+    puzzle.write_jpz(&mut data).unwrap();
+    let puzzle2 = Puzzle::read_jpz(&mut data.as_slice()).unwrap();
+    assert_eq!(puzzle, puzzle2);
+    assert!(puzzle.scramble(1234));
+    assert!(puzzle.write_jpz(&mut vec![]).is_err());
+}
+
+#[test]
+fn test_read() {
+    let jpz = r#"<?xml version="1.0" encoding="UTF-8"?>
+<crossword-compiler-applet xmlns="http://crossword.info/xml/crossword-compiler-applet">
+<rectangular-puzzle xmlns="http://crossword.info/xml/rectangular-puzzle">
+<metadata><title>Tiny</title><creator>Someone</creator></metadata>
+<crossword>
+<grid width="3" height="2">
+<cell x="1" y="1" solution="A" number="1"/>
+<cell x="2" y="1" solution="B" number="2" background-shape="circle"/>
+<cell x="3" y="1" type="block"/>
+<cell x="1" y="2" solution="C" number="3"/>
+<cell x="2" y="2" solution="D"/>
+<cell x="3" y="2" type="block"/>
+</grid>
+<word id="10" x="1-2" y="1"/>
+<word id="11" x="1-2" y="2"/>
+<word id="12"><cells x="1" y="1"/><cells x="1" y="2"/></word>
+<word id="13" x="2" y="1-2"/>
+<clues><title><b>Across</b></title>
+<clue word="10" number="1">First <i>clue</i></clue>
+<clue word="11" number="3">Second</clue>
+</clues>
+<clues><title><b>Down</b></title>
+<clue word="12" number="1">Third</clue>
+<clue word="13" number="2">Fourth</clue>
+</clues>
+</crossword>
+</rectangular-puzzle>
+</crossword-compiler-applet>"#;
+    let puzzle = Puzzle::read_jpz(&mut jpz.as_bytes()).unwrap();
+    assert_eq!(puzzle.title, "Tiny");
+    assert_eq!(puzzle.author, "Someone");
+    assert!(puzzle.grid[(1, 0)].as_ref().unwrap().circled);
+    assert!(puzzle.grid[(2, 0)].is_none());
+    assert_eq!(puzzle.clues.values().cloned().collect::<Vec<_>>(), vec!["First clue", "Second", "Third", "Fourth"]);
+}
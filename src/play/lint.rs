@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+use crate::core::puzzle::{Direction, Window, MIN_LENGTH, regions};
+use crate::core::symmetry::Symmetry;
+use crate::play::puzzle::{Puzzle, PuzzleCell};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Check {
+    ShortEntry,
+    UncheckedCell,
+    Disconnected,
+    Asymmetric,
+    DuplicateAnswer,
+    ClueContainsAnswer,
+    EmptyClue,
+    Rebus,
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Check::ShortEntry => "entry shorter than 3 letters",
+            Check::UncheckedCell => "cell in only one entry",
+            Check::Disconnected => "cells cut off from the rest of the grid",
+            Check::Asymmetric => "cell breaks the symmetry",
+            Check::DuplicateAnswer => "answer used more than once",
+            Check::ClueContainsAnswer => "clue contains its answer",
+            Check::EmptyClue => "clue is empty",
+            Check::Rebus => "rebus is not only capital letters and digits",
+        })
+    }
+}
+
+/// One broken rule. Problems with the grid list the cells to fix; problems with clues
+/// list only their windows.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    pub check: Check,
+    pub windows: Vec<Window>,
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Finding {
+    fn window(check: Check, window: Window, with_cells: bool) -> Self {
+        Finding {
+            check,
+            windows: vec![window],
+            cells: if with_cells { window.positions().collect() } else { vec![] },
+        }
+    }
+
+    fn cell(check: Check, cell: (usize, usize)) -> Self {
+        Finding { check, windows: vec![], cells: vec![cell] }
+    }
+
+    pub fn is_about(&self, window: Option<Window>, cell: (usize, usize)) -> bool {
+        self.cells.contains(&cell) || window.map_or(false, |window| self.windows.contains(&window))
+    }
+
+    /// The finding as a line of text, naming entries by their clue numbers.
+    pub fn describe(&self, puzzle: &Puzzle) -> String {
+        let mut anchors: Vec<String> = self.windows.iter().map(|&window| {
+            format!("{} {}", puzzle.clues.number(window), match window.direction() {
+                Direction::Across => "across",
+                Direction::Down => "down",
+            })
+        }).collect();
+        if self.windows.is_empty() {
+            anchors.extend(self.cells.iter().take(3).map(|(x, y)| format!("({}, {})", x, y)));
+            if self.cells.len() > 3 {
+                anchors.push(format!("{} more", self.cells.len() - 3));
+            }
+        }
+        format!("{}: {}", anchors.join(", "), self.check)
+    }
+}
+
+fn answer(puzzle: &Puzzle, window: Window) -> Option<String> {
+    window.positions().map(|position| match &puzzle.grid[position] {
+        Some(PuzzleCell { solution, .. }) if !solution.is_empty() => Some(solution.to_uppercase()),
+        _ => None,
+    }).collect()
+}
+
+/// Whether some run of consecutive words in `clue` spells `answer`, ignoring case and spaces.
+fn contains_answer(clue: &str, answer: &str) -> bool {
+    let tokens: Vec<String> = clue.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_uppercase())
+        .collect();
+    (0..tokens.len()).any(|start| {
+        let mut joined = String::new();
+        tokens[start..].iter().any(|token| {
+            joined.push_str(token);
+            joined == answer
+        })
+    })
+}
+
+impl Puzzle {
+    /// Checks the grid and clues against the usual rules for publishing a puzzle.
+    pub fn lint(&self, symmetry: Symmetry) -> Vec<Finding> {
+        let white = self.white();
+        let (width, height) = white.size();
+        let cells: Vec<(usize, usize)> = iproduct!(0..height, 0..width)
+            .map(|(y, x)| (x, y))
+            .filter(|&position| white[position])
+            .collect();
+        let mut findings = vec![];
+        findings.extend(self.clues.windows()
+            .filter(|window| window.length() < MIN_LENGTH)
+            .map(|window| Finding::window(Check::ShortEntry, window, true)));
+        findings.extend(cells.iter()
+            .filter(|&&position| {
+                self.clues.window_at(position, Direction::Across).is_none()
+                    || self.clues.window_at(position, Direction::Down).is_none()
+            })
+            .map(|&position| Finding::cell(Check::UncheckedCell, position)));
+        let mut regions = regions(&white);
+        regions.sort_by_key(|region| region.len());
+        regions.pop();
+        findings.extend(regions.into_iter().map(|region| Finding {
+            check: Check::Disconnected,
+            windows: vec![],
+            cells: region,
+        }));
+        findings.extend(symmetry.asymmetric_cells(&white).into_iter()
+            .map(|position| Finding::cell(Check::Asymmetric, position)));
+
+        let mut answers: BTreeMap<String, Vec<Window>> = BTreeMap::new();
+        for window in self.clues.windows() {
+            if let Some(answer) = answer(self, window) {
+                answers.entry(answer).or_insert(vec![]).push(window);
+            }
+        }
+        findings.extend(answers.values().filter(|windows| windows.len() > 1).map(|windows| Finding {
+            check: Check::DuplicateAnswer,
+            windows: windows.clone(),
+            cells: windows.iter().flat_map(|window| window.positions()).collect(),
+        }));
+        for (window, clue) in self.clues.iter() {
+            if clue.trim().is_empty() {
+                findings.push(Finding::window(Check::EmptyClue, window, false));
+            } else if answer(self, window).map_or(false, |answer| contains_answer(clue, &answer)) {
+                findings.push(Finding::window(Check::ClueContainsAnswer, window, false));
+            }
+        }
+
+        findings.extend(cells.iter()
+            .filter(|&&position| {
+                let solution = &self.grid[position].as_ref().unwrap().solution;
+                solution.chars().count() > 1
+                    && !solution.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+            })
+            .map(|&position| Finding::cell(Check::Rebus, position)));
+        findings
+    }
+}
+
+#[test]
+fn test_lint() {
+    use crate::play::puzzle::test_puzzle;
+    use crate::util::grid::Grid;
+    let window = |x, y, length, direction| Window::new((x, y), length, direction);
+    let checks = |puzzle: &Puzzle| puzzle.lint(Symmetry::Rotational).into_iter()
+        .map(|finding| (finding.check, finding.windows, finding.cells))
+        .collect::<Vec<_>>();
+    let fill = |puzzle: &mut Puzzle, letters: &[&str]| {
+        for (y, row) in letters.iter().enumerate() {
+            for (x, letter) in row.chars().enumerate() {
+                if let Some(cell) = puzzle.grid[(x, y)].as_mut() {
+                    cell.solution = letter.to_string();
+                }
+            }
+        }
+    };
+
+    let mut puzzle = test_puzzle();
+    puzzle.grid = Grid::new((3, 3), |_, _| Some(PuzzleCell::default()));
+    puzzle.update_windows();
+    for (_, clue) in puzzle.clues.iter_mut() {
+        *clue = "Clue".to_string();
+    }
+    fill(&mut puzzle, &["CAB", "ORE", "WET"]);
+    assert_eq!(checks(&puzzle), vec![]);
+    puzzle.grid[(0, 0)].as_mut().unwrap().solution = "Ca".to_string();
+    puzzle.clues[window(0, 2, 3, Direction::Across)] = "\"Wet\" paint".to_string();
+    puzzle.clues[window(2, 0, 3, Direction::Down)] = " ".to_string();
+    assert_eq!(checks(&puzzle), vec![
+        (Check::ClueContainsAnswer, vec![window(0, 2, 3, Direction::Across)], vec![]),
+        (Check::EmptyClue, vec![window(2, 0, 3, Direction::Down)], vec![]),
+        (Check::Rebus, vec![], vec![(0, 0)]),
+    ]);
+    assert_eq!(puzzle.lint(Symmetry::Rotational)[0].describe(&puzzle), "5 across: clue contains its answer");
+
+    let mut puzzle = test_puzzle();
+    puzzle.grid[(2, 2)] = None;
+    puzzle.update_windows();
+    fill(&mut puzzle, &["AAA", "A.A", "AA."]);
+    let found = checks(&puzzle);
+    assert!(found.contains(&(Check::ShortEntry, vec![window(0, 2, 2, Direction::Across)], vec![(0, 2), (1, 2)])));
+    assert!(found.contains(&(Check::UncheckedCell, vec![], vec![(2, 1)])));
+    assert!(found.contains(&(Check::Asymmetric, vec![], vec![(0, 0)])));
+    assert!(found.contains(&(Check::DuplicateAnswer,
+                             vec![window(0, 2, 2, Direction::Across), window(2, 0, 2, Direction::Down)],
+                             vec![(0, 2), (1, 2), (2, 0), (2, 1)])));
+
+    puzzle.grid = Grid::new((3, 3), |x, _| if x == 1 { None } else { Some(PuzzleCell::default()) });
+    puzzle.update_windows();
+    assert!(checks(&puzzle).contains(&(Check::Disconnected, vec![], vec![(0, 0), (0, 1), (0, 2)])));
+}
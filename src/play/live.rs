@@ -0,0 +1,154 @@
+use std::sync::{Arc, Mutex};
+
+use crate::core::puzzle::{Cell, Window, WindowMap};
+use crate::core::word::Word;
+use crate::fill::candidates::{Candidates, Suggestion};
+use crate::fill::scored_list::ScoredWord;
+use crate::fill::search::Search;
+use crate::play::dirty::DirtyLoop;
+use crate::util::grid::Grid;
+
+const SUGGESTIONS: usize = 10;
+
+struct Shared {
+    grid: Option<Grid<Cell>>,
+    rebus: Option<Grid<Option<Word>>>,
+    words: Arc<Vec<ScoredWord>>,
+    window: Option<Window>,
+    requested: usize,
+    finished: usize,
+    latest: Option<Candidates>,
+    suggestions: Option<(Window, Vec<Suggestion>)>,
+}
+
+/// Keeps `Candidates` up to date with the grid being edited. The counts after `retain`
+/// are published first, so windows with no words at all show up right away, and are
+/// replaced by the counts after `refine_all` once that finishes. Also suggests words
+/// for the window under the cursor.
+pub struct LiveCandidates {
+    shared: Arc<Mutex<Shared>>,
+    worker: DirtyLoop,
+}
+
+impl LiveCandidates {
+    pub fn new() -> Self {
+        let shared = Arc::new(Mutex::new(Shared {
+            grid: None,
+            rebus: None,
+            words: Arc::new(vec![]),
+            window: None,
+            requested: 0,
+            finished: 0,
+            latest: None,
+            suggestions: None,
+        }));
+        let shared2 = shared.clone();
+        let mut cache: Option<(Grid<Cell>, Grid<Option<Word>>, Arc<Vec<ScoredWord>>, Search)> = None;
+        LiveCandidates {
+            shared,
+            worker: DirtyLoop::new(Box::new(move || {
+                let (grid, rebus, words, window, requested) = {
+                    let lock = shared2.lock().unwrap();
+                    match (&lock.grid, &lock.rebus) {
+                        (Some(grid), Some(rebus)) => (grid.clone(), rebus.clone(), lock.words.clone(), lock.window, lock.requested),
+                        _ => return,
+                    }
+                };
+                let cached = cache.as_ref().map_or(false, |(old_grid, old_rebus, old_words, _)| {
+                    *old_grid == grid && *old_rebus == rebus && Arc::ptr_eq(old_words, &words)
+                });
+                if !cached {
+                    let mut search = Search::with_rebus(
+                        WindowMap::from_grid(&Grid::new(grid.size(), |x, y| grid[(x, y)] != Cell::Black)),
+                        &words,
+                        &rebus);
+                    search.retain(&grid);
+                    shared2.lock().unwrap().latest = Some(search.candidates());
+                    search.refine_all();
+                    shared2.lock().unwrap().latest = Some(search.candidates());
+                    cache = Some((grid, rebus, words, search));
+                }
+                let refined = &cache.as_ref().unwrap().3;
+                let suggestions = window
+                    .filter(|&window| refined.sets.get(window).is_some())
+                    .map(|window| (window, refined.suggestions(window, SUGGESTIONS)));
+                let mut lock = shared2.lock().unwrap();
+                lock.suggestions = suggestions;
+                lock.finished = requested;
+            })),
+        }
+    }
+    /// Starts recomputing if the grid, the word list or the window changed since the last call.
+    pub fn update(&self, grid: Grid<Cell>, rebus: Grid<Option<Word>>, words: &Arc<Vec<ScoredWord>>, window: Option<Window>) {
+        let mut lock = self.shared.lock().unwrap();
+        if lock.grid.as_ref() == Some(&grid) && lock.rebus.as_ref() == Some(&rebus)
+            && Arc::ptr_eq(&lock.words, words) && lock.window == window {
+            return;
+        }
+        lock.grid = Some(grid);
+        lock.rebus = Some(rebus);
+        lock.words = words.clone();
+        lock.window = window;
+        lock.requested += 1;
+        self.worker.mark_dirty();
+    }
+    pub fn latest(&self) -> Option<Candidates> {
+        self.shared.lock().unwrap().latest.clone()
+    }
+    /// Suggestions for `window`, if they have been computed for the current grid.
+    pub fn suggestions(&self, window: Window) -> Option<Vec<Suggestion>> {
+        let lock = self.shared.lock().unwrap();
+        if lock.finished < lock.requested {
+            return None;
+        }
+        match &lock.suggestions {
+            Some((for_window, suggestions)) if *for_window == window => Some(suggestions.clone()),
+            _ => None,
+        }
+    }
+    pub fn is_pending(&self) -> bool {
+        let lock = self.shared.lock().unwrap();
+        lock.finished < lock.requested
+    }
+}
+
+#[cfg(test)]
+fn wait(live: &LiveCandidates) {
+    while live.is_pending() {
+        std::thread::yield_now();
+    }
+}
+
+#[test]
+fn test_live_candidates() {
+    use crate::core::letter::Letter;
+    use crate::core::puzzle::Direction;
+    use crate::fill::scored_list::scored_words;
+    let words = Arc::new(scored_words(&["AB", "AC", "BD", "CD"]));
+    let live = LiveCandidates::new();
+    let across = Window::new((0, 0), 2, Direction::Across);
+    let grid = Grid::new((2, 2), |x, y| if (x, y) == (0, 0) {
+        Cell::White(Letter::from_unicode('A'))
+    } else {
+        Cell::White(None)
+    });
+    let no_rebus = Grid::new((2, 2), |_, _| None);
+    live.update(grid.clone(), no_rebus.clone(), &words, None);
+    wait(&live);
+    assert_eq!(live.latest().unwrap().letters[(1, 1)], Some(1));
+    assert_eq!(live.suggestions(across), None);
+    live.update(grid, no_rebus.clone(), &words, Some(across));
+    wait(&live);
+    assert_eq!(live.suggestions(across).unwrap().len(), 2);
+    live.update(Grid::new((2, 2), |_, _| Cell::White(Letter::from_unicode('D'))), no_rebus, &words, Some(across));
+    wait(&live);
+    assert!(live.latest().unwrap().is_stuck((0, 0)));
+    assert_eq!(live.suggestions(across), Some(vec![]));
+
+    // BC fits the across window on its own but leaves no word for the down window at (1, 0).
+    let words = Arc::new(scored_words(&["AB", "BC"]));
+    live.update(Grid::new((2, 2), |_, _| Cell::White(None)), Grid::new((2, 2), |_, _| None), &words, Some(across));
+    wait(&live);
+    let suggestions: Vec<_> = live.suggestions(across).unwrap().iter().map(|suggestion| suggestion.word.word).collect();
+    assert_eq!(suggestions, vec![Word::from_str("AB").unwrap()]);
+}
@@ -1,7 +1,14 @@
 pub mod dirty;
+pub mod history;
+pub mod html;
 pub mod interface;
+pub mod ipuz;
+pub mod jpz;
+pub mod lint;
+pub mod live;
 pub mod play;
+pub mod print;
 pub mod puzzle;
-pub mod range_split;
 pub mod raw_puzzle;
-pub mod server;
\ No newline at end of file
+pub mod server;
+pub mod text;
\ No newline at end of file
@@ -8,8 +8,30 @@ use crate::core::letter::Letter;
 use crate::fill::dictionary::EditedDictionary;
 use itertools::Itertools;
 use crate::core::word::Word;
-use crate::fill::search::{Search, take_one_result};
+use crate::fill::search::Search;
+use crate::fill::background::{Background, BackgroundFill, Progress};
+use crate::fill::conflict::Conflict;
+use crate::fill::candidates::{Candidates, Suggestion};
+use crate::fill::scored_list::ScoredWord;
+use crate::play::live::LiveCandidates;
+use crate::play::history::History;
+use std::sync::Arc;
 use std::iter;
+use crate::play::raw_puzzle::PlayData;
+use std::time::{Duration, Instant};
+
+const GENERATE_MIN_SCORE: u8 = 30;
+const GENERATE_TIME: Duration = Duration::from_secs(10);
+const GENERATE_THREADS: usize = 4;
+const EXPLAIN_TIME: Duration = Duration::from_secs(5);
+
+/// The cells that `Action::Check` and `Action::Reveal` apply to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scope {
+    Letter,
+    Word,
+    Puzzle,
+}
 
 pub enum Action {
     MoveUp,
@@ -20,21 +42,120 @@ pub enum Action {
     ChangeClue { change: isize },
     Delete,
     ChangeColor,
+    CycleSymmetry,
+    FixSymmetry,
     Generate,
+    GenerateAnother,
+    /// Like `Generate`, but keeps searching for `GENERATE_TIME` and takes the best
+    /// scoring fill instead of the first.
+    GenerateBest,
+    ChooseSuggestion { index: usize },
     Accept,
     Reject,
     TogglePencil,
     ToggleEditClue,
+    PauseTimer,
+    ResumeTimer,
+    Undo,
+    Redo,
+    Check { scope: Scope },
+    Reveal { scope: Scope },
+}
+
+pub struct Generator {
+    pub dictionary: EditedDictionary,
+    /// The seed for the next fill. `Action::GenerateAnother` moves on to the next seed.
+    pub seed: u64,
+    fill: Option<BackgroundFill>,
+    best: bool,
+    fallback: Option<Search>,
+    unscored: Option<Search>,
+    goto_unknown: bool,
+    /// Why the last fill failed, until the puzzle changes.
+    pub conflict: Option<Conflict>,
+    explaining: Option<Background<Option<Conflict>>>,
+    words: Option<Arc<Vec<ScoredWord>>>,
+    live: LiveCandidates,
+}
+
+impl Generator {
+    pub fn new(dictionary: EditedDictionary) -> Self {
+        Generator {
+            dictionary,
+            seed: 0,
+            fill: None,
+            best: false,
+            fallback: None,
+            unscored: None,
+            goto_unknown: false,
+            conflict: None,
+            explaining: None,
+            words: None,
+            live: LiveCandidates::new(),
+        }
+    }
+    /// The dictionary with rejected words removed, rebuilt after `accept` or `reject`.
+    fn words(&mut self) -> Arc<Vec<ScoredWord>> {
+        let dictionary = &self.dictionary;
+        self.words.get_or_insert_with(|| Arc::new(dictionary.build())).clone()
+    }
+    pub fn candidates(&self) -> Option<Candidates> {
+        self.live.latest()
+    }
+    pub fn candidates_pending(&self) -> bool {
+        self.live.is_pending()
+    }
+    pub fn suggestions(&self, window: Window) -> Option<Vec<Suggestion>> {
+        self.live.suggestions(window)
+    }
+    pub fn is_running(&self) -> bool {
+        self.fill.is_some()
+    }
+    pub fn is_explaining(&self) -> bool {
+        self.explaining.is_some()
+    }
+    pub fn progress(&self) -> Option<Progress> {
+        self.fill.as_ref().map(|fill| fill.progress())
+    }
+    fn spawn(&self, search: Search) -> BackgroundFill {
+        let deadline = Instant::now() + GENERATE_TIME;
+        if self.best {
+            BackgroundFill::spawn_best(search, deadline, GENERATE_THREADS)
+        } else {
+            BackgroundFill::spawn(search, Some(deadline), GENERATE_THREADS)
+        }
+    }
 }
 
 pub struct Play<'a> {
     view: &'a mut View,
     puzzle: &'a mut Puzzle,
-    dictionary: Option<&'a mut EditedDictionary>,
+    generator: Option<&'a mut Generator>,
+    history: Option<&'a mut History>,
     view_changed: bool,
     puzzle_changed: bool,
 }
 
+fn rebus(cell: &PuzzleCell) -> Option<Word> {
+    if cell.pencil || cell.solution.is_empty() || Letter::from_str(&cell.solution).is_some() {
+        None
+    } else {
+        Word::from_str(&cell.solution)
+    }
+}
+
+/// Changing a letter marked wrong by `Action::Check` leaves it marked as once wrong.
+fn correct(cell: &mut PuzzleCell) {
+    if cell.is_incorrect {
+        cell.is_incorrect = false;
+        cell.was_incorrect = true;
+    }
+}
+
+fn is_wrong(cell: &PuzzleCell) -> bool {
+    !cell.answer.is_empty() && !cell.answer.eq_ignore_ascii_case(&cell.solution)
+}
+
 fn decrease(state: &mut usize) -> bool {
     if *state > 0 {
         *state -= 1;
@@ -54,11 +175,12 @@ fn increase(state: &mut usize, limit: usize) -> bool {
 }
 
 impl<'a> Play<'a> {
-    pub fn new(view: &'a mut View, puzzle: &'a mut Puzzle, dictionary: Option<&'a mut EditedDictionary>) -> Self {
+    pub fn new(view: &'a mut View, puzzle: &'a mut Puzzle, generator: Option<&'a mut Generator>, history: Option<&'a mut History>) -> Self {
         Play {
             view,
             puzzle,
-            dictionary,
+            generator,
+            history,
             view_changed: false,
             puzzle_changed: false,
         }
@@ -70,6 +192,14 @@ impl<'a> Play<'a> {
         self.puzzle_changed
     }
     pub fn do_action(&mut self, action: Action) {
+        if self.generator.as_ref().map_or(false, |generator| generator.is_running()) {
+            self.cancel_generate();
+            return;
+        }
+        let before = match action {
+            Action::Undo | Action::Redo => None,
+            _ => self.history.as_ref().map(|_| self.puzzle.clone()),
+        };
         match action {
             Action::MoveUp => self.do_move_up(),
             Action::MoveDown => self.do_move_down(),
@@ -79,11 +209,31 @@ impl<'a> Play<'a> {
             Action::ChangeClue { change } => self.do_change_clue(change),
             Action::Delete => self.do_delete(),
             Action::ChangeColor => self.change_color(),
-            Action::Generate => self.generate(),
+            Action::CycleSymmetry => self.cycle_symmetry(),
+            Action::FixSymmetry => self.fix_symmetry(),
+            Action::Generate => self.generate(false),
+            Action::GenerateAnother => self.generate_another(),
+            Action::GenerateBest => self.generate(true),
+            Action::ChooseSuggestion { index } => self.choose_suggestion(index),
             Action::Accept => self.accept(),
             Action::Reject => self.reject(),
             Action::TogglePencil => self.toggle_pencil(),
             Action::ToggleEditClue => self.toggle_edit_clue(),
+            Action::PauseTimer => self.set_timer_running(false),
+            Action::ResumeTimer => self.set_timer_running(true),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Check { scope } => self.check(scope),
+            Action::Reveal { scope } => self.reveal(scope),
+        }
+        if let Some(before) = before {
+            self.record(&before);
+        }
+        if self.puzzle_changed {
+            if let Some(generator) = self.generator.as_mut() {
+                generator.conflict = None;
+                generator.explaining = None;
+            }
         }
     }
     fn do_move_up(&mut self) {
@@ -146,10 +296,17 @@ impl<'a> Play<'a> {
     }
 
     fn do_type_in_grid(&mut self, input: u8) {
-        if let Some(PuzzleCell { answer, solution, pencil, .. }) = &mut self.puzzle.grid[self.view.position] {
-            let active_string = if self.view.mode == Mode::Solving { answer } else { solution };
-            *pencil = self.view.pencil;
-            *active_string = String::from_utf8(vec![input.to_ascii_uppercase()]).unwrap();
+        let solving = self.view.mode == Mode::Solving;
+        if let Some(cell) = &mut self.puzzle.grid[self.view.position] {
+            if !(solving && cell.given) {
+                if solving {
+                    correct(cell);
+                }
+                let PuzzleCell { answer, solution, pencil, .. } = cell;
+                let active_string = if solving { answer } else { solution };
+                *pencil = self.view.pencil;
+                *active_string = String::from_utf8(vec![input.to_ascii_uppercase()]).unwrap();
+            }
             let window = self.puzzle.clues.window_at(self.view.position, self.view.direction).unwrap();
             match self.view.direction {
                 Direction::Across => {
@@ -243,10 +400,13 @@ impl<'a> Play<'a> {
             }
             match &mut self.puzzle.grid[self.view.position] {
                 None => panic!(),
-                Some(PuzzleCell { answer, solution, .. }) => {
-                    let active_string = if self.view.mode == Mode::Solving { answer } else { solution };
-                    *active_string = "".to_string();
+                Some(cell) if self.view.mode == Mode::Solving => {
+                    if !cell.given {
+                        correct(cell);
+                        cell.answer = "".to_string();
+                    }
                 }
+                Some(cell) => cell.solution = "".to_string(),
             }
             self.view_changed = true;
             self.puzzle_changed = true;
@@ -257,22 +417,39 @@ impl<'a> Play<'a> {
         if self.view.mode == Mode::Solving {
             return;
         }
-        if self.puzzle.grid[self.view.position].is_some() {
-            self.puzzle.grid[self.view.position] = None;
-        } else {
-            self.puzzle.grid[self.view.position] = Some(PuzzleCell::default());
+        let black = self.puzzle.grid[self.view.position].is_some();
+        let partner = self.view.symmetry.partner(self.view.position, self.puzzle.grid.size());
+        for position in iter::once(self.view.position).chain(partner) {
+            if black {
+                self.puzzle.grid[position] = None;
+            } else if self.puzzle.grid[position].is_none() {
+                self.puzzle.grid[position] = Some(PuzzleCell::default());
+            }
+        }
+        self.puzzle.update_windows();
+        self.puzzle_changed = true;
+    }
+
+    fn cycle_symmetry(&mut self) {
+        if self.view.mode == Mode::Solving {
+            return;
+        }
+        self.view.symmetry = self.view.symmetry.next();
+        self.view_changed = true;
+    }
+
+    fn fix_symmetry(&mut self) {
+        if self.view.mode == Mode::Solving {
+            return;
+        }
+        if !self.puzzle.fix_symmetry(self.view.symmetry).is_empty() {
+            self.puzzle_changed = true;
         }
-        let new_windows =
-            WindowMap::from_grid(
-                &Grid::new(self.puzzle.grid.size(),
-                           |x, y| self.puzzle.grid[(x, y)].is_some()));
-        self.puzzle.clues = WindowMap::new(new_windows.windows().map(|window| {
-            (window, self.puzzle.clues.get(window).map_or("".to_string(), |clue| clue.clone()))
-        }), self.puzzle.grid.size());
     }
 
-    fn generate(&mut self) {
-        let grid = Grid::new(
+    /// The letters entered so far, leaving out the pencilled-in letters of earlier fills.
+    fn fill_grid(&self) -> Grid<Cell> {
+        Grid::new(
             self.puzzle.grid.size(),
             |x, y|
                 match &self.puzzle.grid[(x, y)] {
@@ -285,35 +462,245 @@ impl<'a> Play<'a> {
                         }
                     }
                 },
-        );
-        let dictionary = self.dictionary.as_ref().unwrap().build();
-        let mut search = Search::new(
+        )
+    }
+
+    /// Cells whose solution is a run of letters or a symbol rather than a single letter.
+    fn rebus_grid(&self) -> Grid<Option<Word>> {
+        Grid::new(self.puzzle.grid.size(), |x, y| self.puzzle.grid[(x, y)].as_ref().and_then(rebus))
+    }
+
+    /// Recomputes the candidate counts shown while editing, in the background.
+    pub fn update_candidates(&mut self) {
+        if self.view.mode != Mode::Editing || self.generator.is_none() {
+            return;
+        }
+        let grid = self.fill_grid();
+        let rebus = self.rebus_grid();
+        let window = self.get_current_window();
+        let generator = self.generator.as_mut().unwrap();
+        let words = generator.words();
+        generator.live.update(grid, rebus, &words, window);
+    }
+
+    fn choose_suggestion(&mut self, index: usize) {
+        if self.view.mode != Mode::Editing {
+            return;
+        }
+        let window = match self.get_current_window() {
+            Some(window) => window,
+            None => return,
+        };
+        let suggestion = match self.generator.as_ref()
+            .and_then(|generator| generator.suggestions(window))
+            .and_then(|suggestions| suggestions.get(index).cloned()) {
+            Some(suggestion) => suggestion,
+            None => return,
+        };
+        for (position, letter) in window.positions().zip(suggestion.word.word.into_iter()) {
+            if let Some(cell) = self.puzzle.grid[position].as_mut() {
+                if rebus(cell).is_none() {
+                    cell.solution = letter.to_unicode().to_string();
+                    cell.pencil = self.view.pencil;
+                }
+            }
+        }
+        self.puzzle_changed = true;
+        self.view_changed = true;
+    }
+
+    fn generate(&mut self, best: bool) {
+        if self.generator.as_ref().unwrap().is_running() {
+            return;
+        }
+        let grid = self.fill_grid();
+        let rebus = self.rebus_grid();
+        let generator = self.generator.as_mut().unwrap();
+        let dictionary = generator.words();
+        let mut search = Search::with_rebus(
             WindowMap::from_grid(
-                &Grid::new(grid.size(), |x, y| grid[(x, y)] != Cell::Black)), &dictionary);
+                &Grid::new(grid.size(), |x, y| grid[(x, y)] != Cell::Black)), &dictionary, &rebus);
         search.retain(&grid);
+        search.seed = Some(generator.seed);
+        let mut scored = search.clone();
+        scored.retain_min_score(GENERATE_MIN_SCORE);
+        scored.refine_all();
         search.refine_all();
-        eprintln!("{:?}", search);
-        let mut result = None;
-        let _ = search.solve(&mut take_one_result(&mut result));
-        if let Some(solution) = result {
-            for y in 0..grid.size().1 {
-                for x in 0..grid.size().0 {
-                    if let Some(cell) = self.puzzle.grid[(x, y)].as_mut() {
-                        let new = iter::once(solution.letter_set((x, y)).unwrap().unique().unwrap().to_unicode()).collect();
-                        if cell.solution != new {
-                            cell.solution = new;
-                            cell.pencil = true;
-                            for &direction in &[Direction::Across, Direction::Down] {
-                                if let Some(window) = self.puzzle.clues.window_at((x, y), direction) {
-                                    self.puzzle.clues[window] = format!("AUTO: {}", self.get_solution_word(window).to_unicode());
-                                }
+        generator.conflict = None;
+        generator.explaining = None;
+        generator.unscored = Some(search.clone());
+        generator.fallback = Some(search);
+        generator.best = best;
+        generator.fill = Some(generator.spawn(scored));
+        self.view_changed = true;
+    }
+
+    fn generate_another(&mut self) {
+        if let Some(generator) = self.generator.as_mut() {
+            generator.seed = generator.seed.wrapping_add(1);
+        }
+        self.generate(false);
+    }
+
+    /// Applies the result of a background fill once it has finished. Falls back to
+    /// ignoring word scores if the scored search found nothing, and to the deepest
+    /// partial fill if that also fails or the fill was canceled. When even the
+    /// unscored search has no fill, looks for the conflict that explains why in the
+    /// background and records it once found.
+    pub fn poll_generate(&mut self) {
+        let generator = match self.generator.as_mut() {
+            Some(generator) => generator,
+            None => return,
+        };
+        if generator.explaining.as_ref().map_or(false, |explaining| explaining.is_finished()) {
+            generator.conflict = generator.explaining.take().unwrap().join();
+            self.view_changed = true;
+        }
+        if !generator.fill.as_ref().map_or(false, |fill| fill.is_finished()) {
+            return;
+        }
+        let fill = generator.fill.take().unwrap();
+        let partial = fill.progress().partial;
+        let canceled = fill.is_canceled();
+        let result = fill.join();
+        let fallback = generator.fallback.take();
+        let grid = match (result, fallback) {
+            (Some(solution), _) => {
+                generator.unscored = None;
+                solution.finish()
+            }
+            (None, Some(fallback)) if !canceled => {
+                generator.fill = Some(generator.spawn(fallback));
+                return;
+            }
+            (None, _) => {
+                let unscored = generator.unscored.take();
+                if !canceled {
+                    generator.explaining = unscored.map(|search| {
+                        Background::spawn(move || search.explain(Instant::now() + EXPLAIN_TIME))
+                    });
+                }
+                partial
+            }
+        };
+        let goto_unknown = generator.goto_unknown;
+        generator.goto_unknown = false;
+        let before = self.puzzle.clone();
+        self.apply_fill(&grid);
+        self.record(&before);
+        if goto_unknown {
+            self.goto_unknown();
+        }
+        self.view_changed = true;
+        self.puzzle_changed = true;
+    }
+
+    fn scope_cells(&self, scope: Scope) -> Vec<(usize, usize)> {
+        match scope {
+            Scope::Letter => vec![self.view.position],
+            Scope::Word => self.get_current_window().map_or(vec![], |window| window.positions().collect()),
+            Scope::Puzzle => {
+                let (width, height) = self.puzzle.grid.size();
+                iproduct!(0..height, 0..width)
+                    .map(|(y, x)| (x, y))
+                    .filter(|&position| self.puzzle.grid[position].is_some())
+                    .collect()
+            }
+        }
+    }
+
+    /// Marks the wrong letters in `scope`, leaving empty cells alone.
+    /// Does nothing while the puzzle is scrambled, as the solution is not known.
+    fn check(&mut self, scope: Scope) {
+        if self.view.mode != Mode::Solving || self.puzzle.is_scrambled() {
+            return;
+        }
+        for position in self.scope_cells(scope) {
+            if let Some(cell) = &mut self.puzzle.grid[position] {
+                if is_wrong(cell) && !cell.is_incorrect {
+                    cell.is_incorrect = true;
+                    self.puzzle_changed = true;
+                }
+            }
+        }
+    }
+
+    /// Fills in the solution for `scope`. As in Across Lite, revealed cells are marked as
+    /// given, and ones that held a wrong letter are also marked as once wrong.
+    fn reveal(&mut self, scope: Scope) {
+        if self.view.mode != Mode::Solving || self.puzzle.is_scrambled() {
+            return;
+        }
+        for position in self.scope_cells(scope) {
+            if let Some(cell) = &mut self.puzzle.grid[position] {
+                if cell.answer.eq_ignore_ascii_case(&cell.solution) {
+                    continue;
+                }
+                if is_wrong(cell) || cell.is_incorrect {
+                    cell.was_incorrect = true;
+                }
+                cell.is_incorrect = false;
+                cell.given = true;
+                cell.pencil = false;
+                cell.answer = cell.solution.clone();
+                self.puzzle_changed = true;
+            }
+        }
+    }
+
+    fn record(&mut self, before: &Puzzle) {
+        if let Some(history) = self.history.as_mut() {
+            history.record(before, self.puzzle);
+        }
+    }
+
+    fn undo(&mut self) {
+        let changed = match self.history.as_mut() {
+            Some(history) => history.undo(self.puzzle),
+            None => false,
+        };
+        if changed {
+            self.view_changed = true;
+            self.puzzle_changed = true;
+        }
+    }
+
+    fn redo(&mut self) {
+        let changed = match self.history.as_mut() {
+            Some(history) => history.redo(self.puzzle),
+            None => false,
+        };
+        if changed {
+            self.view_changed = true;
+            self.puzzle_changed = true;
+        }
+    }
+
+    fn cancel_generate(&mut self) {
+        if let Some(fill) = self.generator.as_ref().and_then(|generator| generator.fill.as_ref()) {
+            fill.cancel();
+        }
+    }
+
+    fn apply_fill(&mut self, fill: &Grid<Cell>) {
+        for y in 0..fill.size().1 {
+            for x in 0..fill.size().0 {
+                if let (Some(cell), Cell::White(Some(letter))) = (self.puzzle.grid[(x, y)].as_mut(), fill[(x, y)]) {
+                    if rebus(cell).is_some() {
+                        continue;
+                    }
+                    let new: String = iter::once(letter.to_unicode()).collect();
+                    if cell.solution != new {
+                        cell.solution = new;
+                        cell.pencil = true;
+                        for &direction in &[Direction::Across, Direction::Down] {
+                            if let Some(window) = self.puzzle.clues.window_at((x, y), direction) {
+                                self.puzzle.clues[window] = format!("AUTO: {}", self.get_solution_word(window).to_unicode());
                             }
                         }
                     }
                 }
             }
-        } else {
-            eprintln!("No solution");
         }
     }
 
@@ -334,7 +721,7 @@ impl<'a> Play<'a> {
 
     fn goto_unknown(&mut self) {
         if let Some(window) = self.puzzle.clues.windows().find(|&window| {
-            self.dictionary.as_ref().unwrap().status(self.get_solution_word(window)) == None
+            self.generator.as_ref().unwrap().dictionary.status(self.get_solution_word(window)) == None
         }) {
             self.view.position = window.position();
             self.view.direction = window.direction();
@@ -344,7 +731,9 @@ impl<'a> Play<'a> {
 
     fn accept(&mut self) {
         let word = self.get_solution_word(self.get_current_window().unwrap());
-        self.dictionary.as_mut().unwrap().set_status(word, Some(true));
+        let generator = self.generator.as_mut().unwrap();
+        generator.dictionary.set_status(word, Some(true));
+        generator.words = None;
         self.goto_unknown();
         self.view_changed = true;
         self.puzzle_changed = true;
@@ -352,9 +741,12 @@ impl<'a> Play<'a> {
 
     fn reject(&mut self) {
         let word = self.get_solution_word(self.get_current_window().unwrap());
-        self.dictionary.as_mut().unwrap().set_status(word, Some(false));
-        self.generate();
-        self.goto_unknown();
+        let generator = self.generator.as_mut().unwrap();
+        generator.dictionary.set_status(word, Some(false));
+        generator.words = None;
+        let best = generator.best;
+        self.generate(best);
+        self.generator.as_mut().unwrap().goto_unknown = true;
         self.view_changed = true;
         self.puzzle_changed = true;
     }
@@ -372,4 +764,66 @@ impl<'a> Play<'a> {
         }
         self.view_changed = true;
     }
-}
\ No newline at end of file
+
+    fn set_timer_running(&mut self, running: bool) {
+        if self.view.mode != Mode::Solving {
+            return;
+        }
+        self.puzzle.play_data.get_or_insert(PlayData { time: 0, running }).running = running;
+        self.view_changed = true;
+        self.puzzle_changed = true;
+    }
+}
+#[test]
+fn test_check_reveal() {
+    use crate::core::symmetry::Symmetry;
+    use crate::play::puzzle::test_puzzle;
+    let mut puzzle = test_puzzle();
+    puzzle.grid[(1, 0)].as_mut().unwrap().answer = "X".to_string();
+    puzzle.grid[(2, 0)].as_mut().unwrap().answer = "".to_string();
+    let mut view = View {
+        position: (1, 0),
+        direction: Direction::Across,
+        mode: Mode::Solving,
+        pencil: false,
+        symmetry: Symmetry::None,
+    };
+    let cell = |puzzle: &Puzzle, position| {
+        let cell = puzzle.grid[position].as_ref().unwrap();
+        (cell.answer.clone(), cell.is_incorrect, cell.was_incorrect, cell.given)
+    };
+
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Check { scope: Scope::Word });
+    assert_eq!(cell(&puzzle, (0, 0)), ("USR".to_string(), true, false, false));
+    assert_eq!(cell(&puzzle, (1, 0)), ("X".to_string(), false, false, false));
+    assert_eq!(cell(&puzzle, (2, 0)), ("".to_string(), false, false, false));
+
+    view.position = (0, 0);
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Type { letter: b'r' });
+    assert_eq!(cell(&puzzle, (0, 0)), ("R".to_string(), false, true, false));
+
+    view.position = (1, 0);
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Reveal { scope: Scope::Puzzle });
+    assert_eq!(cell(&puzzle, (0, 0)), ("REB".to_string(), false, true, true));
+    assert_eq!(cell(&puzzle, (1, 0)), ("X".to_string(), false, false, false));
+    assert_eq!(cell(&puzzle, (2, 0)), ("X".to_string(), false, false, true));
+    assert_eq!(cell(&puzzle, (0, 1)), ("X".to_string(), false, true, true));
+
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Type { letter: b'q' });
+    assert_eq!(cell(&puzzle, (1, 0)), ("Q".to_string(), false, false, false));
+    view.position = (2, 0);
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Type { letter: b'q' });
+    assert_eq!(cell(&puzzle, (2, 0)), ("X".to_string(), false, false, true));
+
+    let mut puzzle = test_puzzle();
+    puzzle.grid[(1, 0)].as_mut().unwrap().answer = "x".to_string();
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Reveal { scope: Scope::Word });
+    assert_eq!(cell(&puzzle, (1, 0)), ("x".to_string(), false, false, false));
+
+    let mut puzzle = test_puzzle();
+    assert!(puzzle.scramble(1234));
+    let scrambled = puzzle.clone();
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Check { scope: Scope::Puzzle });
+    Play::new(&mut view, &mut puzzle, None, None).do_action(Action::Reveal { scope: Scope::Puzzle });
+    assert_eq!(puzzle, scrambled);
+}
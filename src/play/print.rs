@@ -0,0 +1,385 @@
+//! Printable SVG and PDF layouts of a puzzle.
+//!
+//! Pages are US Letter with the title, author and copyright at the top, the grid
+//! below them and the Across and Down clues flowing through three columns onto as
+//! many pages as they need. The PDF has one page per layout page; the SVG stacks
+//! the pages vertically in a single image. Text is set in Helvetica with
+//! WinAnsiEncoding, so the PDF can only show Latin-1 and the typographic quotes,
+//! dashes and other extras of Windows-1252, and replaces anything else with `?`.
+
+use std::io;
+use std::io::Write;
+use crate::core::puzzle::Direction;
+use crate::util::lines::break_lines;
+use super::puzzle::Puzzle;
+
+const PAGE_WIDTH: f64 = 612.0;
+const PAGE_HEIGHT: f64 = 792.0;
+const MARGIN: f64 = 36.0;
+const MAX_CELL: f64 = 30.0;
+const COLUMNS: usize = 3;
+const COLUMN_GAP: f64 = 12.0;
+const CLUE_SIZE: f64 = 9.0;
+const CLUE_LINE: f64 = 11.0;
+const NUMBER_WIDTH: f64 = 16.0;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Anchor {
+    Start,
+    Middle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    Rect { x: f64, y: f64, width: f64, height: f64, fill: Option<f64>, stroke: f64 },
+    Circle { x: f64, y: f64, radius: f64 },
+    Text { x: f64, y: f64, size: f64, bold: bool, anchor: Anchor, text: String },
+}
+
+type Page = Vec<Item>;
+
+fn text(x: f64, y: f64, size: f64, bold: bool, anchor: Anchor, text: &str) -> Item {
+    Item::Text { x, y, size, bold, anchor, text: text.to_string() }
+}
+
+struct Columns {
+    pages: Vec<Page>,
+    column: usize,
+    top: f64,
+    y: f64,
+}
+
+impl Columns {
+    fn width() -> f64 {
+        (PAGE_WIDTH - 2.0 * MARGIN - (COLUMNS - 1) as f64 * COLUMN_GAP) / COLUMNS as f64
+    }
+    fn x(&self) -> f64 {
+        MARGIN + self.column as f64 * (Self::width() + COLUMN_GAP)
+    }
+    /// Moves on to the next column, or the next page if no column of this one has room.
+    /// Only an empty column at the top of a page takes whatever does not fit.
+    fn reserve(&mut self, height: f64) {
+        if self.y + height <= PAGE_HEIGHT - MARGIN || (self.y == self.top && self.top == MARGIN) {
+            return;
+        }
+        self.column += 1;
+        if self.column == COLUMNS || self.top + height > PAGE_HEIGHT - MARGIN {
+            self.column = 0;
+            self.top = MARGIN;
+            self.pages.push(vec![]);
+        }
+        self.y = self.top;
+    }
+    fn push(&mut self, item: Item) {
+        self.pages.last_mut().unwrap().push(item);
+    }
+}
+
+impl Puzzle {
+    fn layout(&self, key: bool) -> io::Result<Vec<Page>> {
+        if key && self.is_scrambled() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot print the key of a scrambled puzzle"));
+        }
+        let mut page = vec![];
+        let mut y = MARGIN;
+        let center = PAGE_WIDTH / 2.0;
+        y += 16.0;
+        page.push(text(center, y, 16.0, true, Anchor::Middle, &self.title));
+        for line in &[&self.author, &self.copyright] {
+            if !line.is_empty() {
+                y += 14.0;
+                page.push(text(center, y, 10.0, false, Anchor::Middle, line));
+            }
+        }
+        y += 14.0;
+
+        let (width, height) = self.grid.size();
+        let cell = MAX_CELL
+            .min((PAGE_WIDTH - 2.0 * MARGIN) / width as f64)
+            .min((PAGE_HEIGHT - MARGIN - y) / height as f64);
+        let left = (PAGE_WIDTH - cell * width as f64) / 2.0;
+        let top = y;
+        let numbers = self.clues.numbers();
+        for cy in 0..height {
+            for cx in 0..width {
+                let (x, y) = (left + cx as f64 * cell, top + cy as f64 * cell);
+                let square = |fill| Item::Rect { x, y, width: cell, height: cell, fill, stroke: 0.5 };
+                match &self.grid[(cx, cy)] {
+                    None => page.push(square(Some(0.0))),
+                    Some(puzzle_cell) => {
+                        page.push(square(if puzzle_cell.shaded { Some(0.8) } else { None }));
+                        if puzzle_cell.circled {
+                            page.push(Item::Circle { x: x + cell / 2.0, y: y + cell / 2.0, radius: cell / 2.0 - 1.0 });
+                        }
+                        if let Some(number) = numbers[(cx, cy)] {
+                            let size = cell * 0.3;
+                            page.push(text(x + 1.5, y + size, size, false, Anchor::Start, &number.to_string()));
+                        }
+                        let solution = &puzzle_cell.solution;
+                        if key && !solution.is_empty() {
+                            let count = solution.chars().count() as f64;
+                            let size = (cell * 0.6).min(cell * 0.9 / (count * 0.6));
+                            page.push(text(x + cell / 2.0, y + cell * 0.85, size, false, Anchor::Middle, solution));
+                        }
+                    }
+                }
+            }
+        }
+        page.push(Item::Rect { x: left, y: top, width: cell * width as f64, height: cell * height as f64, fill: None, stroke: 1.5 });
+
+        let top = top + cell * height as f64 + 18.0;
+        let mut columns = Columns { pages: vec![page], column: 0, top, y: top };
+        let graphemes = ((Columns::width() - NUMBER_WIDTH) / (CLUE_SIZE * 0.5)) as usize;
+        for &(direction, heading) in &[(Direction::Across, "ACROSS"), (Direction::Down, "DOWN")] {
+            let mut first = true;
+            for (window, clue) in self.clues.iter().filter(|(window, _)| window.direction() == direction) {
+                let lines: Vec<&str> = break_lines(clue, graphemes).collect();
+                let lines_height = lines.len().max(1) as f64 * CLUE_LINE;
+                if first {
+                    if columns.y != columns.top {
+                        columns.y += CLUE_LINE / 2.0;
+                    }
+                    columns.reserve(CLUE_LINE * 1.5 + lines_height);
+                    columns.y += CLUE_LINE;
+                    let x = columns.x();
+                    columns.push(text(x, columns.y, 10.0, true, Anchor::Start, heading));
+                    columns.y += CLUE_LINE / 2.0;
+                    first = false;
+                } else {
+                    columns.reserve(lines_height);
+                }
+                let x = columns.x();
+                let number = numbers[window.position()].unwrap().to_string();
+                columns.push(text(x, columns.y + CLUE_LINE, CLUE_SIZE, true, Anchor::Start, &number));
+                for line in &lines {
+                    columns.y += CLUE_LINE;
+                    columns.push(text(x + NUMBER_WIDTH, columns.y, CLUE_SIZE, false, Anchor::Start, line.trim_end()));
+                }
+                if lines.is_empty() {
+                    columns.y += CLUE_LINE;
+                }
+            }
+        }
+        Ok(columns.pages)
+    }
+
+    pub fn write_svg(&self, write: &mut dyn Write, key: bool) -> io::Result<()> {
+        let pages = self.layout(key)?;
+        let height = PAGE_HEIGHT * pages.len() as f64;
+        writeln!(write, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(write,
+                 "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}pt\" height=\"{h}pt\" viewBox=\"0 0 {w} {h}\" \
+                 font-family=\"Helvetica, Arial, sans-serif\">",
+                 w = PAGE_WIDTH, h = height)?;
+        writeln!(write, "<title>{}</title>", escape_xml(&self.title))?;
+        writeln!(write, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", PAGE_WIDTH, height)?;
+        for (index, page) in pages.iter().enumerate() {
+            writeln!(write, "<g transform=\"translate(0 {})\">", index as f64 * PAGE_HEIGHT)?;
+            for item in page {
+                match item {
+                    Item::Rect { x, y, width, height, fill, stroke } => {
+                        let fill = fill.map_or("none".to_string(), |gray| {
+                            let level = (gray * 255.0).round() as u8;
+                            format!("rgb({},{},{})", level, level, level)
+                        });
+                        writeln!(write, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"black\" stroke-width=\"{}\"/>",
+                                 x, y, width, height, fill, stroke)?;
+                    }
+                    Item::Circle { x, y, radius } => {
+                        writeln!(write, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"0.5\"/>",
+                                 x, y, radius)?;
+                    }
+                    Item::Text { x, y, size, bold, anchor, text } => {
+                        writeln!(write, "<text x=\"{}\" y=\"{}\" font-size=\"{}\"{}{}>{}</text>",
+                                 x, y, size,
+                                 if *bold { " font-weight=\"bold\"" } else { "" },
+                                 if *anchor == Anchor::Middle { " text-anchor=\"middle\"" } else { "" },
+                                 escape_xml(text))?;
+                    }
+                }
+            }
+            writeln!(write, "</g>")?;
+        }
+        writeln!(write, "</svg>")?;
+        Ok(())
+    }
+
+    pub fn write_pdf(&self, write: &mut dyn Write, key: bool) -> io::Result<()> {
+        let pages = self.layout(key)?;
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".as_bytes().to_vec(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+                    (0..pages.len()).map(|index| format!("{} 0 R", 5 + index * 2)).collect::<Vec<_>>().join(" "),
+                    pages.len()).into_bytes(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_vec(),
+        ];
+        for (index, page) in pages.iter().enumerate() {
+            let content = pdf_content(page);
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH, PAGE_HEIGHT, 6 + index * 2).into_bytes());
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend_from_slice(&content);
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut data = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = vec![];
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(data.len());
+            data.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            data.extend_from_slice(object);
+            data.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = data.len();
+        data.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            data.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        data.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+        write.write_all(&data)
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The characters WinAnsiEncoding puts at 0x80 to 0x9F, where Latin-1 has control codes.
+static WIN_ANSI: [(char, u8); 27] = [
+    ('\u{20AC}', 0x80), ('\u{201A}', 0x82), ('\u{0192}', 0x83), ('\u{201E}', 0x84), ('\u{2026}', 0x85),
+    ('\u{2020}', 0x86), ('\u{2021}', 0x87), ('\u{02C6}', 0x88), ('\u{2030}', 0x89), ('\u{0160}', 0x8A),
+    ('\u{2039}', 0x8B), ('\u{0152}', 0x8C), ('\u{017D}', 0x8E), ('\u{2018}', 0x91), ('\u{2019}', 0x92),
+    ('\u{201C}', 0x93), ('\u{201D}', 0x94), ('\u{2022}', 0x95), ('\u{2013}', 0x96), ('\u{2014}', 0x97),
+    ('\u{02DC}', 0x98), ('\u{2122}', 0x99), ('\u{0161}', 0x9A), ('\u{203A}', 0x9B), ('\u{0153}', 0x9C),
+    ('\u{017E}', 0x9E), ('\u{0178}', 0x9F),
+];
+
+fn win_ansi(c: char) -> u8 {
+    match c as u32 {
+        0x20..=0x7E | 0xA0..=0xFF => c as u8,
+        _ => WIN_ANSI.iter().find(|&&(other, _)| other == c).map_or(b'?', |&(_, byte)| byte),
+    }
+}
+
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut result = vec![b'('];
+    for c in text.chars() {
+        let byte = win_ansi(c);
+        if byte == b'(' || byte == b')' || byte == b'\\' {
+            result.push(b'\\');
+        }
+        result.push(byte);
+    }
+    result.push(b')');
+    result
+}
+
+fn pdf_content(page: &Page) -> Vec<u8> {
+    let mut content = vec![];
+    for item in page {
+        match item {
+            Item::Rect { x, y, width, height, fill, stroke } => {
+                let rect = format!("{:.2} {:.2} {:.2} {:.2} re", x, PAGE_HEIGHT - y - height, width, height);
+                let paint = match fill {
+                    Some(gray) => format!("{:.2} g {} B", gray, rect),
+                    None => format!("{} S", rect),
+                };
+                content.extend_from_slice(format!("{:.2} w {}\n", stroke, paint).as_bytes());
+            }
+            Item::Circle { x, y, radius } => {
+                let (x, y) = (*x, PAGE_HEIGHT - y);
+                let k = radius * 0.5523;
+                content.extend_from_slice(format!(
+                    "0.50 w {:.2} {:.2} m {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c \
+                     {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c S\n",
+                    x + radius, y,
+                    x + radius, y + k, x + k, y + radius, x, y + radius,
+                    x - k, y + radius, x - radius, y + k, x - radius, y,
+                    x - radius, y - k, x - k, y - radius, x, y - radius,
+                    x + k, y - radius, x + radius, y - k, x + radius, y).as_bytes());
+            }
+            Item::Text { x, y, size, bold, anchor, text } => {
+                // Treating every Helvetica glyph as 0.6 em wide is close enough to
+                // center grid letters.
+                let x = match anchor {
+                    Anchor::Start => *x,
+                    Anchor::Middle => x - text.chars().count() as f64 * size * 0.3,
+                };
+                content.extend_from_slice(format!(
+                    "0 g BT /{} {:.2} Tf {:.2} {:.2} Td ", if *bold { "F2" } else { "F1" }, size, x, PAGE_HEIGHT - y).as_bytes());
+                content.extend_from_slice(&pdf_string(text));
+                content.extend_from_slice(b" Tj ET\n");
+            }
+        }
+    }
+    content
+}
+
+#[test]
+fn test_svg() {
+    let puzzle = super::puzzle::test_puzzle();
+    let mut blank = vec![];
+    puzzle.write_svg(&mut blank, false).unwrap();
+    let blank = String::from_utf8(blank).unwrap();
+    assert!(blank.contains(">Title</text>"));
+    assert!(blank.contains(">ACROSS</text>"));
+    assert!(blank.contains(">clue 3</text>"));
+    assert!(blank.contains("<circle"));
+    assert!(!blank.contains(">REB</text>"));
+    let mut key = vec![];
+    puzzle.write_svg(&mut key, true).unwrap();
+    assert!(String::from_utf8(key).unwrap().contains(">REB</text>"));
+}
+
+#[test]
+fn test_pdf() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    for index in 0..puzzle.clues.len() {
+        let window = puzzle.clues.windows().nth(index).unwrap();
+        puzzle.clues[window] = "A long (clue) that wraps ".repeat(200);
+    }
+    let mut data = vec![];
+    puzzle.write_pdf(&mut data, true).unwrap();
+    let text = String::from_utf8_lossy(&data);
+    assert!(text.starts_with("%PDF-1.4"));
+    assert!(!text.contains("/Count 1 "));
+    assert!(text.contains("(A long \\(clue\\) that wraps"));
+    let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+    let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+    assert!(data[xref..].starts_with(b"xref\n"));
+}
+
+#[test]
+fn test_pdf_string() {
+    assert_eq!(pdf_string("It\u{2019}s \u{201C}caf\u{E9}\u{201D} \u{2013} 1\u{2026}"), b"(It\x92s \x93caf\xE9\x94 \x96 1\x85)".to_vec());
+    assert_eq!(pdf_string("\u{0085}\u{3042}(\\)"), b"(??\\(\\\\\\))".to_vec());
+}
+
+#[test]
+fn test_tall_grid() {
+    use crate::core::puzzle::WindowMap;
+    use crate::util::grid::Grid;
+    use super::puzzle::PuzzleCell;
+    let mut puzzle = super::puzzle::test_puzzle();
+    puzzle.grid = Grid::new((15, 25), |_, _| Some(PuzzleCell::default()));
+    let windows = WindowMap::from_grid(&Grid::new((15, 25), |_, _| true));
+    puzzle.clues = WindowMap::new(windows.windows().map(|window| (window, "clue".to_string())), (15, 25));
+    let pages = puzzle.layout(false).unwrap();
+    assert!(pages.len() > 1);
+    for item in pages.iter().flatten() {
+        if let Item::Text { y, .. } = item {
+            assert!(*y <= PAGE_HEIGHT - MARGIN);
+        }
+    }
+}
+
+#[test]
+fn test_scrambled_key() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    assert!(puzzle.scramble(1234));
+    assert!(puzzle.write_pdf(&mut vec![], true).is_err());
+    assert!(puzzle.write_svg(&mut vec![], false).is_ok());
+}
@@ -10,18 +10,20 @@ use std::fmt::Debug;
 use std::fmt;
 use std::fmt::Formatter;
 use std::io;
-use super::raw_puzzle::RawPuzzle;
+use std::iter;
+use super::raw_puzzle::{RawPuzzle, PuzError, PlayData};
 use std::io::Write;
 use std::fs;
 use std::ops::Range;
-use super::range_split::RangeSplitExt;
+use crate::util::range_split::RangeSplitExt;
 use super::raw_puzzle::RawHeader;
 use std::ffi::OsStr;
 use std::collections::{HashMap, BTreeSet};
 use std::collections::BTreeMap;
 use crate::util::grid::Grid;
 use crate::core::puzzle::{Direction, Window, WindowMap};
-use crate::play::raw_puzzle::MAGIC;
+use crate::core::symmetry::Symmetry;
+use crate::play::raw_puzzle::{MAGIC, SCRAMBLED, UTF8_VERSION, TextEncoding};
 
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Mode {
@@ -38,6 +40,8 @@ pub struct View {
     pub direction: Direction,
     pub mode: Mode,
     pub pencil: bool,
+    /// Black squares toggled in the editor also toggle their partner under this symmetry.
+    pub symmetry: Symmetry,
 }
 
 #[derive(Eq, Ord, PartialEq, PartialOrd, Clone, Debug)]
@@ -49,6 +53,7 @@ pub struct PuzzleCell {
     pub is_incorrect: bool,
     pub given: bool,
     pub circled: bool,
+    pub shaded: bool,
 }
 
 impl Default for PuzzleCell {
@@ -61,6 +66,7 @@ impl Default for PuzzleCell {
             is_incorrect: false,
             given: false,
             circled: false,
+            shaded: false,
         }
     }
 }
@@ -75,6 +81,11 @@ pub struct Puzzle {
     pub grid: Grid<Option<PuzzleCell>>,
     pub clues: WindowMap<String>,
     pub note: String,
+    pub scrambled_checksum: Option<u16>,
+    pub unknown_sections: Vec<([u8; 4], Vec<u8>)>,
+    /// See `RawPuzzle::section_order`.
+    pub section_order: Vec<[u8; 4]>,
+    pub play_data: Option<PlayData>,
 }
 
 /*pub fn windows<T>(grid: &Grid<T>, mut black: impl FnMut(&T) -> bool) -> Vec<Window> {
@@ -126,7 +137,14 @@ fn string_to_letter(string: &str) -> Option<u8> {
 }
 
 impl Puzzle {
-    fn from_raw(raw: RawPuzzle) -> Self {
+    fn from_raw(raw: RawPuzzle) -> Result<Self, PuzError> {
+        if let (Some(rebus_index), Some(rebus_data)) = (&raw.rebus_index, &raw.rebus_data) {
+            if let Some(&missing) = rebus_index.iter().find(|&&index| {
+                index.checked_sub(1).map_or(false, |index| !rebus_data.contains_key(&index))
+            }) {
+                return Err(PuzError::MalformedRebusTable(format!("{}", missing - 1)));
+            }
+        }
         let grid =
             Grid::new(
                 raw.solution.size(),
@@ -145,14 +163,14 @@ impl Puzzle {
                             }.unwrap_or(if solution_letter == b'-' {
                                 String::new()
                             } else {
-                                String::from_utf8(vec![solution_letter]).unwrap()
+                                (solution_letter as char).to_string()
                             });
                             let mut answer = "".to_string();
                             if let Some(rebus_user) = &raw.rebus_user {
                                 answer = rebus_user[(x, y)].to_string();
                             }
                             if answer == "" && raw_answer != b'-' {
-                                answer = String::from_utf8(vec![raw_answer]).unwrap();
+                                answer = (raw_answer as char).to_string();
                             }
                             Some(PuzzleCell {
                                 solution,
@@ -162,6 +180,7 @@ impl Puzzle {
                                 given: (style & 0x40) != 0,
                                 circled: (style & 0x80) != 0,
                                 pencil: (style & 0x08) != 0,
+                                shaded: false,
                             })
                         }
                     });
@@ -169,7 +188,9 @@ impl Puzzle {
             grid[(x, y)].is_some()
         })).windows().collect();
         windows.sort_by_key(|win| { (win.position().1, win.position().0, win.direction()) });
-        assert_eq!(windows.len(), raw.clues.len());
+        if windows.len() != raw.clues.len() {
+            return Err(PuzError::ClueCountMismatch { expected: windows.len(), actual: raw.clues.len() });
+        }
         let clues =
             WindowMap::new(
                 windows.iter()
@@ -185,12 +206,94 @@ impl Puzzle {
             grid: grid,
             clues,
             note: raw.note,
+            scrambled_checksum: if raw.header.scrambled != 0 {
+                Some(raw.header.scrambled_checksum)
+            } else {
+                None
+            },
+            unknown_sections: raw.unknown_sections,
+            section_order: raw.section_order,
+            play_data: raw.play_data,
         };
-        return result;
+        Ok(result)
+    }
+
+    pub fn encoding(&self) -> TextEncoding {
+        TextEncoding::for_version(self.version)
+    }
+
+    pub fn upgrade_version(&mut self) -> bool {
+        let encoding = self.encoding();
+        if self.into_raw().strings().all(|string| encoding.can_encode(string)) {
+            return false;
+        }
+        self.version = UTF8_VERSION;
+        true
+    }
+
+    pub fn advance_timer(&mut self, seconds: usize) {
+        if let Some(PlayData { time, running: true }) = &mut self.play_data {
+            *time += seconds;
+        }
+    }
+
+    pub fn white(&self) -> Grid<bool> {
+        Grid::new(self.grid.size(), |x, y| self.grid[(x, y)].is_some())
+    }
+
+    /// Rebuilds `clues` after black squares change, keeping the clues of windows that are still there.
+    pub fn update_windows(&mut self) {
+        let new_windows = WindowMap::from_grid(&self.white());
+        self.clues = WindowMap::new(new_windows.windows().map(|window| {
+            (window, self.clues.get(window).map_or("".to_string(), |clue| clue.clone()))
+        }), self.grid.size());
+    }
+
+    /// Blacks out the white cells whose partner under `symmetry` is black, and returns them.
+    pub fn fix_symmetry(&mut self, symmetry: Symmetry) -> Vec<(usize, usize)> {
+        let cells = symmetry.asymmetric_cells(&self.white());
+        for &cell in cells.iter() {
+            self.grid[cell] = None;
+        }
+        if !cells.is_empty() {
+            self.update_windows();
+        }
+        cells
     }
 
-    pub fn read_from(read: &mut dyn BufRead) -> io::Result<Puzzle> {
-        Ok(Self::from_raw(RawPuzzle::read_from(read)?))
+    pub fn is_scrambled(&self) -> bool {
+        self.scrambled_checksum.is_some()
+    }
+
+    pub fn find_scramble_key(&self) -> Option<u16> {
+        self.into_raw().find_scramble_key()
+    }
+
+    pub fn unscramble(&mut self, key: u16) -> bool {
+        let mut raw = self.into_raw();
+        if !raw.unscramble(key) {
+            return false;
+        }
+        *self = Self::from_raw(raw).unwrap();
+        true
+    }
+
+    pub fn scramble(&mut self, key: u16) -> bool {
+        let mut raw = self.into_raw();
+        if !raw.scramble(key) {
+            return false;
+        }
+        *self = Self::from_raw(raw).unwrap();
+        true
+    }
+
+    pub fn read_from(read: &mut dyn BufRead) -> Result<Puzzle, PuzError> {
+        Self::from_raw(RawPuzzle::read_from(read)?)
+    }
+
+    pub fn read_lenient(read: &mut dyn BufRead) -> Result<(Puzzle, Vec<PuzError>), PuzError> {
+        let (raw, warnings) = RawPuzzle::read_lenient(read)?;
+        Ok((Self::from_raw(raw)?, warnings))
     }
 
     pub fn into_raw(&self) -> RawPuzzle {
@@ -278,6 +381,8 @@ impl Puzzle {
                 width: self.grid.size().0 as u8,
                 height: self.grid.size().1 as u8,
                 clues: clues.len() as u16,
+                scrambled_checksum: self.scrambled_checksum.unwrap_or(0),
+                scrambled: if self.scrambled_checksum.is_some() { SCRAMBLED } else { 0 },
             },
             solution: Grid::new(self.grid.size(), |x, y| {
                 match &self.grid[(x, y)] {
@@ -296,7 +401,9 @@ impl Puzzle {
             rebus_index: rebus_index,
             rebus_data: rebus_data,
             rebus_user: rebus_user,
-            play_data: None,
+            play_data: self.play_data.clone(),
+            unknown_sections: self.unknown_sections.clone(),
+            section_order: self.section_order.clone(),
             style: style,
             title: self.title.clone(),
             author: self.author.clone(),
@@ -330,9 +437,9 @@ impl Puzzle {
 //    }
 //}
 
-#[test]
-fn test_encode_decode() {
-    let puzzle = Puzzle {
+#[cfg(test)]
+pub fn test_puzzle() -> Puzzle {
+    Puzzle {
         preamble: vec![],
         version: *b"1.4\0",
         title: "Title".to_string(),
@@ -358,6 +465,7 @@ fn test_encode_decode() {
                     is_incorrect: false,
                     given: false,
                     circled: x == 2 && y == 0,
+                    shaded: false,
                 })
             }
         }),
@@ -370,9 +478,149 @@ fn test_encode_decode() {
             (window, format!("clue {}", index))
         }), (3, 3)),
         note: "Note".to_string(),
-    };
+        scrambled_checksum: None,
+        unknown_sections: vec![],
+        section_order: vec![],
+        play_data: None,
+    }
+}
+
+#[test]
+fn test_encode_decode() {
+    let puzzle = test_puzzle();
     let mut data = vec![];
     puzzle.clone().write_to(&mut &mut data).unwrap();
     let puzzle2 = Puzzle::read_from(&mut data.as_slice()).unwrap();
     assert_eq!(puzzle, puzzle2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_scramble() {
+    let letters = b"CRANEHOTELAROSEMETERSTEAK";
+    let puzzle = Puzzle {
+        grid: Grid::new((5, 5), |x, y| Some(PuzzleCell {
+            solution: (letters[x + y * 5] as char).to_string(),
+            ..PuzzleCell::default()
+        })),
+        clues: WindowMap::new(WindowMap::from_grid(&Grid::new((5, 5), |x, y| true))
+                                  .windows().zip(iter::repeat("".to_string())), (5, 5)),
+        ..test_puzzle()
+    };
+    let mut scrambled = puzzle.clone();
+    assert!(scrambled.scramble(4071));
+    assert!(scrambled.is_scrambled());
+    let mut twice = scrambled.clone();
+    assert!(!twice.scramble(1234));
+    assert_eq!(twice, scrambled);
+    assert_ne!(puzzle.grid, scrambled.grid);
+    let mut data = vec![];
+    scrambled.clone().write_to(&mut &mut data).unwrap();
+    let mut scrambled2 = Puzzle::read_from(&mut data.as_slice()).unwrap();
+    assert_eq!(scrambled, scrambled2);
+    assert!(!scrambled2.clone().unscramble(1234));
+    assert_eq!(Some(4071), scrambled2.find_scramble_key());
+    assert!(scrambled2.unscramble(4071));
+    assert!(!scrambled2.is_scrambled());
+    assert_eq!(puzzle, scrambled2);
+}
+
+
+#[test]
+fn test_read_errors() {
+    let mut data = vec![];
+    test_puzzle().write_to(&mut &mut data).unwrap();
+    match Puzzle::read_from(&mut &data[1..]) {
+        Err(PuzError::BadMagic) => {}
+        other => panic!("{:?}", other),
+    }
+    match Puzzle::read_from(&mut &data[..60]) {
+        Err(PuzError::TruncatedSection(section)) => assert_eq!(section, "solution"),
+        other => panic!("{:?}", other),
+    }
+    let mut corrupt = data.clone();
+    let title = corrupt.windows(5).position(|window| window == b"Title").unwrap();
+    corrupt[title] = b't';
+    match Puzzle::read_from(&mut corrupt.as_slice()) {
+        Err(PuzError::ChecksumMismatch { section, .. }) => assert_eq!(section, "file"),
+        other => panic!("{:?}", other),
+    }
+    let (puzzle, warnings) = Puzzle::read_lenient(&mut corrupt.as_slice()).unwrap();
+    assert_eq!(puzzle.title, "title");
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn test_unknown_sections() {
+    let mut puzzle = test_puzzle();
+    puzzle.unknown_sections = vec![(*b"ZZZZ", b"first".to_vec()), (*b"AAAA", vec![]), (*b"MMMM", vec![1, 2, 3])];
+    let mut data = vec![];
+    puzzle.clone().write_to(&mut &mut data).unwrap();
+    let mut puzzle2 = Puzzle::read_from(&mut data.as_slice()).unwrap();
+    assert_eq!(puzzle, puzzle2);
+    puzzle2.title = "Edited".to_string();
+    let mut data2 = vec![];
+    puzzle2.clone().write_to(&mut &mut data2).unwrap();
+    assert_eq!(data[data.len() - 30..], data2[data2.len() - 30..]);
+    assert_eq!(puzzle2, Puzzle::read_from(&mut data2.as_slice()).unwrap());
+}
+
+#[test]
+fn test_section_order() {
+    let mut puzzle = test_puzzle();
+    puzzle.unknown_sections = vec![(*b"XXXX", b"between".to_vec())];
+    puzzle.section_order = vec![*b"GRBS", *b"XXXX", *b"RTBL", *b"GEXT", *b"RUSR"];
+    let mut data = vec![];
+    puzzle.clone().write_to(&mut &mut data).unwrap();
+    let find = |name: &[u8]| data.windows(4).position(|window| window == name).unwrap();
+    assert!(find(b"GRBS") < find(b"XXXX") && find(b"XXXX") < find(b"RTBL"));
+    assert_eq!(puzzle, Puzzle::read_from(&mut data.as_slice()).unwrap());
+}
+
+#[test]
+fn test_utf8() {
+    let mut puzzle = test_puzzle();
+    puzzle.title = "Café".to_string();
+    assert!(!puzzle.upgrade_version());
+    let mut data = vec![];
+    puzzle.clone().write_to(&mut &mut data).unwrap();
+    assert!(data.windows(5).any(|window| window == b"Caf\xE9\0"));
+    assert_eq!(puzzle, Puzzle::read_from(&mut data.as_slice()).unwrap());
+
+    puzzle.clues[Window::new((0, 0), 3, Direction::Across)] = "Tokyo, in 日本".to_string();
+    assert_eq!(puzzle.clone().write_to(&mut vec![]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert!(puzzle.upgrade_version());
+    assert_eq!(puzzle.encoding(), TextEncoding::Utf8);
+    let mut data = vec![];
+    puzzle.clone().write_to(&mut &mut data).unwrap();
+    assert!(data.windows(6).any(|window| window == "Café\0".as_bytes()));
+    assert_eq!(puzzle, Puzzle::read_from(&mut data.as_slice()).unwrap());
+}
+#[test]
+fn test_timer() {
+    let mut puzzle = test_puzzle();
+    puzzle.advance_timer(5);
+    assert_eq!(puzzle.play_data, None);
+    puzzle.play_data = Some(PlayData { time: 90, running: true });
+    puzzle.advance_timer(5);
+    let mut data = vec![];
+    puzzle.clone().write_to(&mut &mut data).unwrap();
+    assert!(data.windows(5).any(|window| window == b"95,0\0"));
+    assert_eq!(puzzle, Puzzle::read_from(&mut data.as_slice()).unwrap());
+    puzzle.play_data.as_mut().unwrap().running = false;
+    puzzle.advance_timer(5);
+    let mut data = vec![];
+    puzzle.clone().write_to(&mut &mut data).unwrap();
+    assert_eq!(Puzzle::read_from(&mut data.as_slice()).unwrap().play_data, Some(PlayData { time: 95, running: false }));
+}
+
+#[test]
+fn test_fix_symmetry() {
+    let mut puzzle = test_puzzle();
+    assert!(puzzle.fix_symmetry(Symmetry::Rotational).is_empty());
+    puzzle.grid[(2, 1)] = None;
+    puzzle.update_windows();
+    assert_eq!(puzzle.fix_symmetry(Symmetry::Rotational), vec![(0, 1)]);
+    assert!(puzzle.grid[(0, 1)].is_none());
+    assert_eq!(puzzle.clues.len(), 2);
+    assert_eq!(puzzle.clues[Window::new((0, 0), 3, Direction::Across)], "clue 0");
+}
@@ -1,4 +1,4 @@
-use std::{io, fs, iter};
+use std::{io, fs, iter, fmt, error};
 use std::io::BufRead;
 use byteorder::LittleEndian;
 use encoding::{Encoding, DecoderTrap};
@@ -19,14 +19,51 @@ use std::ops::Range;
 use encoding::types::DecoderTrap::Call;
 
 pub static MAGIC: [u8; 12] = *b"ACROSS&DOWN\x00";
+pub static SCRAMBLED: u16 = 0x0004;
+pub static UTF8_VERSION: [u8; 4] = *b"2.0\0";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextEncoding {
+    Latin1,
+    Utf8,
+}
+
+impl TextEncoding {
+    pub fn for_version(version: [u8; 4]) -> Self {
+        if version[0].is_ascii_digit() && version[0] >= b'2' {
+            TextEncoding::Utf8
+        } else {
+            TextEncoding::Latin1
+        }
+    }
+    pub fn can_encode(self, string: &str) -> bool {
+        match self {
+            TextEncoding::Latin1 => string.chars().all(|c| (c as u32) < 256),
+            TextEncoding::Utf8 => true,
+        }
+    }
+    pub fn encode(self, string: &str) -> io::Result<Vec<u8>> {
+        match self {
+            TextEncoding::Latin1 => match ISO_8859_1.encode(string, EncoderTrap::Strict) {
+                Ok(encoded) => Ok(encoded),
+                Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            },
+            TextEncoding::Utf8 => Ok(string.as_bytes().to_vec()),
+        }
+    }
+    fn decode(self, bytes: &[u8]) -> Result<String, String> {
+        match self {
+            TextEncoding::Latin1 => ISO_8859_1.decode(bytes, DecoderTrap::Strict).map_err(|e| e.into_owned()),
+            TextEncoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string()),
+        }
+    }
+}
 
 pub struct Checksums {
     pub file_checksum: u16,
     pub cib_checksum: u16,
     pub magic_checksum: [u8; 8],
     pub magic: [u8; 12],
-    pub scrambled_checksum: u16,
-    pub scrambled: u16,
     pub bitmask: u16,
 }
 
@@ -39,12 +76,14 @@ pub struct RawHeader {
     pub width: u8,
     pub height: u8,
     pub clues: u16,
+    pub scrambled_checksum: u16,
+    pub scrambled: u16,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PlayData {
-    time: usize,
-    running: bool,
+    pub time: usize,
+    pub running: bool,
 }
 
 #[derive(Debug)]
@@ -62,19 +101,127 @@ pub struct RawPuzzle {
     pub rebus_data: Option<BTreeMap<u8, String>>,
     pub play_data: Option<PlayData>,
     pub rebus_user: Option<Grid<String>>,
+    pub unknown_sections: Vec<([u8; 4], Vec<u8>)>,
+    /// The order the extension sections were read in, or empty if it was the order
+    /// `write_to` uses anyway: the known sections first, then the unknown ones.
+    pub section_order: Vec<[u8; 4]>,
+}
+
+#[derive(Debug)]
+pub enum PuzError {
+    Io(io::Error),
+    BadMagic,
+    ChecksumMismatch {
+        section: String,
+        expected: u64,
+        actual: u64,
+    },
+    TruncatedSection(String),
+    MalformedSection(String),
+    MalformedRebusTable(String),
+    BadString(String),
+    UnsupportedBitmask(u16),
+    TrailingData(usize),
+    ClueCountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl PuzError {
+    fn truncated(section: &str) -> impl FnOnce(io::Error) -> PuzError + '_ {
+        move |error| {
+            if error.kind() == ErrorKind::UnexpectedEof {
+                PuzError::TruncatedSection(section.to_string())
+            } else {
+                PuzError::Io(error)
+            }
+        }
+    }
+    fn checksum(section: &str, expected: u64, actual: u64) -> Result<(), PuzError> {
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(PuzError::ChecksumMismatch { section: section.to_string(), expected, actual })
+        }
+    }
+}
+
+impl fmt::Display for PuzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzError::Io(error) => write!(f, "{}", error),
+            PuzError::BadMagic => write!(f, "not an Across Lite file"),
+            PuzError::ChecksumMismatch { section, expected, actual } =>
+                write!(f, "bad {} checksum: expected {:#X}, found {:#X}", section, expected, actual),
+            PuzError::TruncatedSection(section) => write!(f, "truncated {} section", section),
+            PuzError::MalformedSection(section) => write!(f, "malformed {} section", section),
+            PuzError::MalformedRebusTable(entry) => write!(f, "malformed rebus table entry {:?}", entry),
+            PuzError::BadString(error) => write!(f, "bad string: {}", error),
+            PuzError::UnsupportedBitmask(bitmask) => write!(f, "unsupported bitmask {:#X}", bitmask),
+            PuzError::TrailingData(length) => write!(f, "{} bytes of trailing data", length),
+            PuzError::ClueCountMismatch { expected, actual } =>
+                write!(f, "grid has {} clues but file has {}", expected, actual),
+        }
+    }
+}
+
+impl error::Error for PuzError {}
+
+impl From<io::Error> for PuzError {
+    fn from(error: io::Error) -> Self {
+        PuzError::Io(error)
+    }
+}
+
+impl From<PuzError> for io::Error {
+    fn from(error: PuzError) -> Self {
+        match error {
+            PuzError::Io(error) => error,
+            error => io::Error::new(ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+fn checksum_to_u64(checksum: [u8; 8]) -> u64 {
+    u64::from_le_bytes(checksum)
 }
 
 struct PuzzleReader<'a> {
     input: &'a [u8],
+    encoding: TextEncoding,
+    lenient: bool,
+    warnings: Vec<PuzError>,
 }
 
 impl<'a> PuzzleReader<'a> {
+    fn new(input: &'a [u8], encoding: TextEncoding, lenient: bool) -> Self {
+        PuzzleReader {
+            input,
+            encoding,
+            lenient,
+            warnings: vec![],
+        }
+    }
+    fn recover(&mut self, result: Result<(), PuzError>) -> Result<(), PuzError> {
+        match result {
+            Err(error) if self.lenient => {
+                self.warnings.push(error);
+                Ok(())
+            }
+            result => result,
+        }
+    }
     fn read_fixed<B: AsMut<[u8]>>(&mut self, mut buf: B) -> io::Result<B> {
         self.input.read_exact(buf.as_mut())?;
         Ok(buf)
     }
-    fn read_raw_header(&mut self) -> io::Result<(RawHeader, Checksums)> {
-        let magic_position = self.input.fill_buf()?.windows(MAGIC.len()).position(|window| window == MAGIC).unwrap();
+    fn read_raw_header(&mut self) -> Result<(RawHeader, Checksums), PuzError> {
+        let magic_position =
+            self.input.windows(MAGIC.len())
+                .position(|window| window == MAGIC)
+                .filter(|&position| position >= 2)
+                .ok_or(PuzError::BadMagic)?;
         let mut preamble = vec![0; magic_position - 2];
         self.input.read_exact(&mut preamble)?;
         let file_checksum = self.input.read_u16::<LittleEndian>()?;
@@ -98,13 +245,13 @@ impl<'a> PuzzleReader<'a> {
             width,
             height,
             clues,
+            scrambled_checksum,
+            scrambled,
         }, Checksums {
             file_checksum,
             cib_checksum,
             magic_checksum,
             magic,
-            scrambled,
-            scrambled_checksum,
             bitmask,
         }))
     }
@@ -117,19 +264,55 @@ impl<'a> PuzzleReader<'a> {
         }
         Ok(grid)
     }
-    fn read_string(&mut self) -> io::Result<String> {
+    fn read_string(&mut self) -> Result<String, PuzError> {
         let mut vec = vec![];
         self.input.read_until(0, &mut vec)?;
-        vec.truncate(vec.len() - 1);
-        match ISO_8859_1.decode(&vec, DecoderTrap::Strict) {
-            Ok(x) => Ok(x),
-            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e))
+        if vec.pop() != Some(0) {
+            return Err(PuzError::TruncatedSection("strings".to_string()));
         }
+        self.encoding.decode(&vec).map_err(PuzError::BadString)
     }
-    fn read_raw_puzzle(&mut self) -> io::Result<RawPuzzle> {
-        let (header, checksums) = self.read_raw_header()?;
-        let solution = self.read_grid((header.width as usize, header.height as usize))?;
-        let answer = self.read_grid((header.width as usize, header.height as usize))?;
+    fn read_rebus_table(&self, content: &[u8]) -> Result<BTreeMap<u8, String>, PuzError> {
+        let mut rebus_data = BTreeMap::<u8, String>::new();
+        for pair in content.split(|&c| c == b';') {
+            if pair != b"" {
+                let malformed = || PuzError::MalformedRebusTable(String::from_utf8_lossy(pair).into_owned());
+                let mut elements = pair.split(|&c| c == b':');
+                let key = elements.next().ok_or_else(malformed)?;
+                let value = elements.next().ok_or_else(malformed)?;
+                if elements.next().is_some() {
+                    return Err(malformed());
+                }
+                let key = std::str::from_utf8(key).ok()
+                    .and_then(|key| key.trim_start_matches(' ').parse::<u8>().ok())
+                    .ok_or_else(malformed)?;
+                let value = self.encoding.decode(value).map_err(|_| malformed())?;
+                rebus_data.insert(key, value);
+            }
+        }
+        Ok(rebus_data)
+    }
+    fn read_play_data(content: &[u8]) -> Result<PlayData, PuzError> {
+        let malformed = || PuzError::MalformedSection("LTIM".to_string());
+        let mut split = std::str::from_utf8(content).map_err(|_| malformed())?.split(",");
+        let result = PlayData {
+            time: split.next().and_then(|time| time.parse().ok()).ok_or_else(malformed)?,
+            running: split.next().and_then(|stopped| stopped.parse::<u8>().ok()).ok_or_else(malformed)? == 0,
+        };
+        if split.next().is_some() {
+            return Err(malformed());
+        }
+        Ok(result)
+    }
+    fn read_raw_puzzle(&mut self) -> Result<RawPuzzle, PuzError> {
+        let (header, checksums) = self.read_raw_header().map_err(|error| match error {
+            PuzError::Io(error) => PuzError::truncated("header")(error),
+            error => error,
+        })?;
+        let size = (header.width as usize, header.height as usize);
+        self.encoding = header.encoding();
+        let solution = self.read_grid(size).map_err(PuzError::truncated("solution"))?;
+        let answer = self.read_grid(size).map_err(PuzError::truncated("answer"))?;
         let title = self.read_string()?;
         let author = self.read_string()?;
         let copyright = self.read_string()?;
@@ -152,86 +335,85 @@ impl<'a> PuzzleReader<'a> {
             style: None,
             rebus_user: None,
             play_data: None,
+            unknown_sections: vec![],
+            section_order: vec![],
         };
-        loop {
-            let name = match self.read_fixed([0u8; 4]) {
-                Ok(name) => name,
-                Err(e) => {
-                    if e.kind() == ErrorKind::UnexpectedEof {
-                        break;
-                    } else { return Err(e); }
-                }
-            };
+        let mut order = vec![];
+        while !self.input.is_empty() {
+            if self.input.len() < 8 {
+                return Err(PuzError::TrailingData(self.input.len()));
+            }
+            let name = self.read_fixed([0u8; 4])?;
+            let section = String::from_utf8_lossy(&name).into_owned();
             let length = self.input.read_u16::<LittleEndian>()?;
             let checksum = self.input.read_u16::<LittleEndian>()?;
             let mut content = vec![0u8; length as usize];
-            self.input.read_exact(&mut content)?;
-            assert_eq!(0, self.input.read_u8()?);
+            self.input.read_exact(&mut content).map_err(PuzError::truncated(&section))?;
+            if self.input.read_u8().map_err(PuzError::truncated(&section))? != 0 {
+                return Err(PuzError::MalformedSection(section));
+            }
+            let mut actual_checksum = Checksum(0);
+            actual_checksum.write_all(&content)?;
+            let checksum_result = PuzError::checksum(&section, checksum as u64, actual_checksum.0 as u64);
+            self.recover(checksum_result)?;
+            order.push(name);
             match &name {
                 b"GRBS" => {
                     result.rebus_index =
                         Some(
-                            PuzzleReader { input: &mut (&content as &[u8]) }
-                                .read_grid((header.width as usize, header.height as usize))?)
+                            PuzzleReader::new(&content, self.encoding, self.lenient)
+                                .read_grid(size)
+                                .map_err(PuzError::truncated(&section))?)
                 }
                 b"RTBL" => {
-                    let mut rebus_data = BTreeMap::<u8, String>::new();
-                    for pair in content.split(|&c| c == b';') {
-                        if pair != b"" {
-                            let mut elements = pair.split(|&c| c == b':');
-                            let mut key = elements.next().unwrap();
-                            while key[0] == b' ' {
-                                key = &key[1..];
-                            }
-                            let value = elements.next().unwrap();
-                            assert!(elements.next().is_none());
-                            rebus_data.insert(std::str::from_utf8(key).unwrap().parse::<u8>().unwrap(),
-                                              ISO_8859_1.decode(value, DecoderTrap::Strict).unwrap());
-                        }
-                    }
-                    result.rebus_data = Some(rebus_data);
+                    result.rebus_data = Some(self.read_rebus_table(&content)?);
                 }
                 b"GEXT" => {
                     result.style =
                         Some(
-                            PuzzleReader { input: &mut (&content as &[u8]) }
-                                .read_grid((header.width as usize, header.height as usize))?)
+                            PuzzleReader::new(&content, self.encoding, self.lenient)
+                                .read_grid(size)
+                                .map_err(PuzError::truncated(&section))?)
                 }
                 b"LTIM" => {
-                    let mut split = std::str::from_utf8(&content).unwrap().split(",");
-                    result.play_data = Some(PlayData {
-                        time: split.next().unwrap().parse().unwrap(),
-                        running: split.next().unwrap().parse::<u8>().unwrap() == 1,
-                    });
-                    assert!(split.next().is_none());
+                    result.play_data = Some(Self::read_play_data(&content)?);
                 }
                 b"RUSR" => {
-                    let mut reader = PuzzleReader { input: &mut (&content as &[u8]) };
+                    let mut reader = PuzzleReader::new(&content, self.encoding, self.lenient);
                     let mut cells =
                         iter::repeat_with(|| reader.read_string())
-                            .take(result.solution.size().0 * result.solution.size().1)
-                            .collect::<io::Result<Vec<_>>>()?.into_iter();
+                            .take(size.0 * size.1)
+                            .collect::<Result<Vec<_>, PuzError>>()?.into_iter();
                     result.rebus_user = Some(
-                        Grid::new(result.solution.size(),
-                                  |x, y| cells.next().unwrap())
+                        Grid::new(size, |x, y| cells.next().unwrap())
                     );
-                    let mut unused = vec![];
-                    reader.input.read_to_end(&mut unused)?;
-                    assert!(unused.len() == 0);
+                    if !reader.input.is_empty() {
+                        return Err(PuzError::MalformedSection(section));
+                    }
                 }
-                x => panic!("Can't handle {:?}", String::from_utf8(name.to_vec()))
+                _ => result.unknown_sections.push((name, content)),
             }
         }
-        let mut unparsed = vec![];
-        self.input.read_to_end(&mut unparsed)?;
-        assert_eq!(Vec::<u8>::new(), unparsed);
-        assert_eq!(result.compute_cib_checksum(), checksums.cib_checksum);
-        assert_eq!(result.compute_file_checksum(), checksums.file_checksum);
-        assert_eq!(result.compute_magic_checksum(), checksums.magic_checksum);
-        assert_eq!(checksums.magic, MAGIC);
-        assert_eq!(0, checksums.scrambled);
-        assert_eq!(0, checksums.scrambled_checksum);
-        assert_eq!(1, checksums.bitmask);
+        if order != result.default_section_order() {
+            result.section_order = order;
+        }
+        if checksums.magic != MAGIC {
+            return Err(PuzError::BadMagic);
+        }
+        let cib_checksum = PuzError::checksum(
+            "CIB", checksums.cib_checksum as u64, result.compute_cib_checksum() as u64);
+        self.recover(cib_checksum)?;
+        let file_checksum = PuzError::checksum(
+            "file", checksums.file_checksum as u64, result.compute_file_checksum() as u64);
+        self.recover(file_checksum)?;
+        let magic_checksum = PuzError::checksum(
+            "masked",
+            checksum_to_u64(checksums.magic_checksum),
+            checksum_to_u64(result.compute_magic_checksum()));
+        self.recover(magic_checksum)?;
+        if checksums.bitmask != 1 {
+            self.recover(Err(PuzError::UnsupportedBitmask(checksums.bitmask)))?;
+        }
         Ok(result)
     }
 }
@@ -251,17 +433,6 @@ impl Write for Checksum {
     }
 }
 
-fn encode_string(string: &str) -> io::Result<Vec<u8>> {
-    match ISO_8859_1.encode(string, EncoderTrap::Strict) {
-        Ok(encoded) => {
-            Ok(encoded)
-        }
-        Err(e) => {
-            Err(io::Error::new(io::ErrorKind::InvalidData, e))?
-        }
-    }
-}
-
 
 trait PuzzleWriter: Write {
     fn write_header(&mut self, puzzle: &RawPuzzle) -> io::Result<()> {
@@ -273,13 +444,13 @@ trait PuzzleWriter: Write {
         self.write_all(&puzzle.compute_magic_checksum())?;
         self.write_all(&header.version)?;
         self.write_all(&header.reserved1)?;
-        self.write_u16::<LittleEndian>(0)?;
+        self.write_u16::<LittleEndian>(header.scrambled_checksum)?;
         self.write_all(&header.reserved2)?;
         self.write_u8(header.width)?;
         self.write_u8(header.height)?;
         self.write_u16::<LittleEndian>(header.clues)?;
         self.write_u16::<LittleEndian>(1)?;
-        self.write_u16::<LittleEndian>(0)?;
+        self.write_u16::<LittleEndian>(header.scrambled)?;
         Ok(())
     }
 
@@ -293,23 +464,29 @@ trait PuzzleWriter: Write {
     }
 
 
-    fn write_string(&mut self, string: &str) -> io::Result<()> {
-        self.write_all(&encode_string(&string)?)?;
+    fn write_string(&mut self, string: &str, encoding: TextEncoding) -> io::Result<()> {
+        self.write_all(&encoding.encode(&string)?)?;
         self.write_u8(0)?;
         Ok(())
     }
 
     fn write_raw_puzzle(&mut self, puzzle: &RawPuzzle) -> io::Result<()> {
+        let encoding = puzzle.header.encoding();
+        if let Some(string) = puzzle.strings().find(|string| !encoding.can_encode(string)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} cannot be encoded in version {:?}", string, String::from_utf8_lossy(&puzzle.header.version))));
+        }
         self.write_header(&puzzle)?;
         self.write_grid(&puzzle.solution)?;
         self.write_grid(&puzzle.answer)?;
-        self.write_string(&puzzle.title)?;
-        self.write_string(&puzzle.author)?;
-        self.write_string(&puzzle.copyright)?;
+        self.write_string(&puzzle.title, encoding)?;
+        self.write_string(&puzzle.author, encoding)?;
+        self.write_string(&puzzle.copyright, encoding)?;
         for clue in puzzle.clues.iter() {
-            self.write_string(clue)?;
+            self.write_string(clue, encoding)?;
         }
-        self.write_string(&puzzle.note)?;
+        self.write_string(&puzzle.note, encoding)?;
         let mut extras = Vec::<([u8; 4], Vec<u8>)>::new();
         if let Some(ref rebus_index) = puzzle.rebus_index {
             let mut data = vec![];
@@ -330,22 +507,25 @@ trait PuzzleWriter: Write {
                     write!(&mut data, "{}", rebus_index)?;
                 }
                 write!(&mut data, ":")?;
-                data.extend_from_slice(&ISO_8859_1.encode(rebus, EncoderTrap::Strict).unwrap());
+                data.extend_from_slice(&encoding.encode(rebus)?);
                 write!(&mut data, ";")?;
             }
             extras.push((*b"RTBL", data));
         }
         if let Some(ref play_data) = puzzle.play_data {
-            extras.push((*b"LTIM", format!("{},{}", play_data.time, play_data.running as u8).into_bytes()));
+            extras.push((*b"LTIM", format!("{},{}", play_data.time, !play_data.running as u8).into_bytes()));
         }
         if let Some(ref rebus_user) = puzzle.rebus_user {
             let mut data = vec![];
             for rebus in rebus_user.iter() {
-                data.extend_from_slice(&ISO_8859_1.encode(rebus, EncoderTrap::Strict).unwrap());
+                data.extend_from_slice(&encoding.encode(rebus)?);
                 data.extend_from_slice(&[0]);
             }
             extras.push((*b"RUSR", data));
         }
+        extras.extend(puzzle.unknown_sections.iter().cloned());
+        let order = &puzzle.section_order;
+        extras.sort_by_key(|(name, _)| order.iter().position(|other| other == name).unwrap_or(order.len()));
         for (name, data) in extras {
             self.write_all(&name)?;
             self.write_u16::<LittleEndian>(data.len() as u16)?;
@@ -366,34 +546,194 @@ trait PuzzleWriter: Write {
 
 impl<W> PuzzleWriter for W where W: Write {}
 
+// Across Lite scrambles the solution column by column, skipping black squares.
+fn scrambled_positions(grid: &Grid<u8>) -> Vec<(usize, usize)> {
+    let mut result = vec![];
+    for x in 0..grid.size().0 {
+        for y in 0..grid.size().1 {
+            if grid[(x, y)] != b'.' {
+                result.push((x, y));
+            }
+        }
+    }
+    result
+}
+
+fn key_digits(key: u16) -> [usize; 4] {
+    [(key / 1000 % 10) as usize, (key / 100 % 10) as usize, (key / 10 % 10) as usize, (key % 10) as usize]
+}
+
+fn shift_letters(letters: &mut [u8], digits: &[usize; 4], forward: bool) {
+    for (index, letter) in letters.iter_mut().enumerate() {
+        if letter.is_ascii_uppercase() {
+            let digit = digits[index % digits.len()];
+            let shift = if forward { digit } else { 26 - digit };
+            *letter = b'A' + ((*letter - b'A') as usize + shift) as u8 % 26;
+        }
+    }
+}
+
+fn shuffle_letters(letters: &[u8]) -> Vec<u8> {
+    let middle = letters.len() / 2;
+    let mut result = Vec::with_capacity(letters.len());
+    for index in 0..middle {
+        result.push(letters[middle + index]);
+        result.push(letters[index]);
+    }
+    if letters.len() % 2 == 1 {
+        result.push(letters[letters.len() - 1]);
+    }
+    result
+}
+
+fn unshuffle_letters(letters: &[u8]) -> Vec<u8> {
+    letters.iter().skip(1).step_by(2).chain(letters.iter().step_by(2)).cloned().collect()
+}
+
+pub fn scramble_letters(mut letters: Vec<u8>, key: u16) -> Vec<u8> {
+    let digits = key_digits(key);
+    if letters.is_empty() {
+        return letters;
+    }
+    for &digit in digits.iter() {
+        shift_letters(&mut letters, &digits, true);
+        // Like Across Lite and puzpy (`s[k:] + s[:k]`), a digit longer than the letters
+        // leaves them unrotated.
+        if digit <= letters.len() {
+            letters.rotate_left(digit);
+        }
+        letters = shuffle_letters(&letters);
+    }
+    letters
+}
+
+pub fn unscramble_letters(mut letters: Vec<u8>, key: u16) -> Vec<u8> {
+    let digits = key_digits(key);
+    if letters.is_empty() {
+        return letters;
+    }
+    for &digit in digits.iter().rev() {
+        letters = unshuffle_letters(&letters);
+        if digit <= letters.len() {
+            letters.rotate_right(digit);
+        }
+        shift_letters(&mut letters, &digits, false);
+    }
+    letters
+}
+
+impl RawHeader {
+    pub fn encoding(&self) -> TextEncoding {
+        TextEncoding::for_version(self.version)
+    }
+}
+
 impl RawPuzzle {
+    pub fn strings(&self) -> impl Iterator<Item=&String> {
+        iter::once(&self.title)
+            .chain(iter::once(&self.author))
+            .chain(iter::once(&self.copyright))
+            .chain(self.clues.iter())
+            .chain(iter::once(&self.note))
+            .chain(self.rebus_data.iter().flat_map(|rebus_data| rebus_data.values()))
+            .chain(self.rebus_user.iter().flat_map(|rebus_user| rebus_user.iter()))
+    }
+
+    fn default_section_order(&self) -> Vec<[u8; 4]> {
+        let known = [
+            (*b"GRBS", self.rebus_index.is_some()),
+            (*b"GEXT", self.style.is_some()),
+            (*b"RTBL", self.rebus_data.is_some()),
+            (*b"LTIM", self.play_data.is_some()),
+            (*b"RUSR", self.rebus_user.is_some()),
+        ];
+        known.iter().filter(|(_, present)| *present).map(|(name, _)| *name)
+            .chain(self.unknown_sections.iter().map(|(name, _)| *name))
+            .collect()
+    }
+
     fn text_checksum(&self, checksum: &mut Checksum) {
+        let encoding = self.header.encoding();
         if !self.title.is_empty() {
-            checksum.write_string(&self.title).unwrap();
+            checksum.write_string(&self.title, encoding).unwrap();
         }
         if !self.author.is_empty() {
-            checksum.write_string(&self.author).unwrap();
+            checksum.write_string(&self.author, encoding).unwrap();
         }
         if !self.copyright.is_empty() {
-            checksum.write_string(&self.copyright).unwrap();
+            checksum.write_string(&self.copyright, encoding).unwrap();
         }
         for clue in self.clues.iter() {
             if !clue.is_empty() {
-                checksum.write_all(&(encode_string(&clue).unwrap())).unwrap();
+                checksum.write_all(&(encoding.encode(&clue).unwrap())).unwrap();
             }
         }
         if !self.note.is_empty() {
-            checksum.write_string(&self.note).unwrap();
+            checksum.write_string(&self.note, encoding).unwrap();
+        }
+    }
+
+    pub fn is_scrambled(&self) -> bool {
+        self.header.scrambled != 0
+    }
+
+    fn solution_letters(&self) -> Vec<u8> {
+        scrambled_positions(&self.solution).into_iter().map(|position| self.solution[position]).collect()
+    }
+
+    fn set_solution_letters(&mut self, letters: Vec<u8>) {
+        for (position, letter) in scrambled_positions(&self.solution).into_iter().zip(letters) {
+            self.solution[position] = letter;
         }
     }
 
+    fn compute_scrambled_checksum(letters: &[u8]) -> u16 {
+        let mut checksum = Checksum(0);
+        checksum.write_all(letters).unwrap();
+        checksum.0
+    }
+
+    pub fn check_scramble_key(&self, key: u16) -> bool {
+        self.is_scrambled() &&
+            Self::compute_scrambled_checksum(&unscramble_letters(self.solution_letters(), key))
+                == self.header.scrambled_checksum
+    }
+
+    pub fn find_scramble_key(&self) -> Option<u16> {
+        (0..10000).find(|&key| self.check_scramble_key(key))
+    }
+
+    pub fn unscramble(&mut self, key: u16) -> bool {
+        if !self.check_scramble_key(key) {
+            return false;
+        }
+        let letters = unscramble_letters(self.solution_letters(), key);
+        self.set_solution_letters(letters);
+        self.header.scrambled = 0;
+        self.header.scrambled_checksum = 0;
+        true
+    }
+
+    /// Scrambles the solution with `key`. Returns false, leaving the puzzle alone, if it
+    /// is already scrambled.
+    pub fn scramble(&mut self, key: u16) -> bool {
+        if self.is_scrambled() {
+            return false;
+        }
+        let letters = self.solution_letters();
+        self.header.scrambled_checksum = Self::compute_scrambled_checksum(&letters);
+        self.header.scrambled = SCRAMBLED;
+        self.set_solution_letters(scramble_letters(letters, key));
+        true
+    }
+
     fn compute_cib_checksum(&self) -> u16 {
         let mut checksum = Checksum(0);
         checksum.write_u8(self.header.width).unwrap();
         checksum.write_u8(self.header.height).unwrap();
         checksum.write_u16::<LittleEndian>(self.header.clues).unwrap();
         checksum.write_u16::<LittleEndian>(1).unwrap();
-        checksum.write_u16::<LittleEndian>(0).unwrap();
+        checksum.write_u16::<LittleEndian>(self.header.scrambled).unwrap();
         checksum.0
     }
 
@@ -435,12 +775,18 @@ impl RawPuzzle {
     }
 
 
-    pub fn read_from(read: &mut dyn BufRead) -> io::Result<RawPuzzle> {
+    pub fn read_from(read: &mut dyn BufRead) -> Result<RawPuzzle, PuzError> {
         let mut buffer = vec![];
         read.read_to_end(&mut buffer)?;
-        PuzzleReader {
-            input: &buffer,
-        }.read_raw_puzzle()
+        PuzzleReader::new(&buffer, TextEncoding::Latin1, false).read_raw_puzzle()
+    }
+
+    pub fn read_lenient(read: &mut dyn BufRead) -> Result<(RawPuzzle, Vec<PuzError>), PuzError> {
+        let mut buffer = vec![];
+        read.read_to_end(&mut buffer)?;
+        let mut reader = PuzzleReader::new(&buffer, TextEncoding::Latin1, true);
+        let result = reader.read_raw_puzzle()?;
+        Ok((result, reader.warnings))
     }
 
     pub fn write_to(&self, mut write: &mut dyn Write) -> io::Result<()> {
@@ -449,6 +795,27 @@ impl RawPuzzle {
     }
 }
 
+#[test]
+fn test_scramble_letters() {
+    for &length in &[1, 2, 7, 12, 25] {
+        let letters: Vec<u8> = (0..length).map(|index| b'A' + (index * 7 % 26) as u8).collect();
+        for &key in &[0, 1234, 9999, 4071] {
+            let scrambled = scramble_letters(letters.clone(), key);
+            assert_eq!(letters.len(), scrambled.len());
+            assert_eq!(letters, unscramble_letters(scrambled, key));
+        }
+    }
+    // Computed with puzpy's `scramble_string`.
+    for &(letters, key, scrambled) in &[
+        ("ABCDEFGHIJKLMNOPQRSTUVWXY", 1234, "SBPYJXHUASYPZGYHRBOYPWGYD"),
+        ("CROSSWORDPUZZLE", 9876, "JTVAEQFHYSWXETW"),
+        ("CAT", 4567, "USR"),
+    ] {
+        assert_eq!(scramble_letters(letters.as_bytes().to_vec(), key), scrambled.as_bytes());
+        assert_eq!(unscramble_letters(scrambled.as_bytes().to_vec(), key), letters.as_bytes());
+    }
+}
+
 //#[test]
 //fn test_read() {
 //    for filename_result in fs::read_dir("puzzles").unwrap() {
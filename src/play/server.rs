@@ -31,8 +31,10 @@ use crate::play::play::Play;
 use crate::play::interface::{TerminalInput, start_rendering, TerminalOutput, stop_rendering};
 use crate::play::puzzle::{Puzzle, View, Mode};
 use crate::play::dirty::DirtyLoop;
+use crate::play::history::History;
 use crate::util::bag::Bag;
 use crate::core::puzzle::Direction;
+use crate::core::symmetry::Symmetry;
 
 
 struct Game {
@@ -44,6 +46,7 @@ struct EventLoop {
     input: TcpStream,
     game: Arc<Mutex<Game>>,
     view: Arc<Mutex<View>>,
+    history: History,
     render_loop: Arc<DirtyLoop>,
 }
 
@@ -52,7 +55,7 @@ impl EventLoop {
         while let Some(action) = (TerminalInput { input: &mut self.input }.read_event()?) {
             let mut view = self.view.lock().unwrap();
             let mut game = self.game.lock().unwrap();
-            let mut play = Play::new(&mut *view, &mut game.puzzle, None);
+            let mut play = Play::new(&mut *view, &mut game.puzzle, None, Some(&mut self.history));
             play.do_action(action);
             if play.puzzle_changed() {
                 for listener in game.listeners.into_iter() {
@@ -73,7 +76,8 @@ fn handle(mut stream: TcpStream, game1: Arc<Mutex<Game>>) -> io::Result<()> {
         position: (0, 0),
         direction: Direction::Across,
         mode: Mode::Solving,
-        pencil: false
+        pencil: false,
+        symmetry: Symmetry::None,
     }));
     let view2 = view.clone();
     let game2 = game1.clone();
@@ -81,7 +85,7 @@ fn handle(mut stream: TcpStream, game1: Arc<Mutex<Game>>) -> io::Result<()> {
     let render_loop = Arc::new(DirtyLoop::new(Box::new(move || {
         let view_clone = view2.lock().unwrap().clone();
         let puzzle_clone = game2.lock().unwrap().puzzle.clone();
-        TerminalOutput { output: &mut output, view: &view_clone, puzzle: &puzzle_clone }.render();
+        TerminalOutput { output: &mut output, view: &view_clone, puzzle: &puzzle_clone, progress: None, conflict: None, candidates: None, suggestions: None, findings: None }.render();
     })));
     let render_token = game1.lock().unwrap().listeners.insert(render_loop.clone());
     render_loop.mark_dirty();
@@ -89,6 +93,7 @@ fn handle(mut stream: TcpStream, game1: Arc<Mutex<Game>>) -> io::Result<()> {
         input: input,
         game: game1.clone(),
         view: view,
+        history: History::new(),
         render_loop: render_loop,
     };
     event_loop.run();
@@ -0,0 +1,267 @@
+use std::{fmt, error, io};
+use std::io::{BufRead, Write};
+use std::collections::{BTreeSet, HashMap};
+use crate::util::grid::Grid;
+use crate::core::puzzle::{Direction, WindowMap};
+use super::puzzle::{Puzzle, PuzzleCell};
+
+static REBUS_MARKERS: &str = "1234567890@#$%^&*+=?";
+
+#[derive(Debug)]
+pub struct TextError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl TextError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        TextError { line, message: message.into() }
+    }
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for TextError {}
+
+impl From<TextError> for io::Error {
+    fn from(error: TextError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+struct Section {
+    line: usize,
+    lines: Vec<(usize, String)>,
+}
+
+impl Section {
+    fn content(&self) -> impl Iterator<Item=&(usize, String)> {
+        self.lines.iter().filter(|(_, line)| !line.is_empty())
+    }
+    fn single(&self) -> String {
+        self.content().map(|(_, line)| line.as_str()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+impl Puzzle {
+    pub fn read_text(read: &mut dyn BufRead) -> Result<Puzzle, TextError> {
+        let mut sections = HashMap::<String, Section>::new();
+        let mut current: Option<String> = None;
+        let mut header = false;
+        for (index, line) in read.lines().enumerate() {
+            let number = index + 1;
+            let line = line.map_err(|error| TextError::new(number, error.to_string()))?;
+            let trimmed = line.trim();
+            if !header {
+                match trimmed {
+                    "" => continue,
+                    "<ACROSS PUZZLE>" | "<ACROSS PUZZLE V2>" => {
+                        header = true;
+                        continue;
+                    }
+                    _ => return Err(TextError::new(number, "expected <ACROSS PUZZLE>")),
+                }
+            }
+            if trimmed.starts_with('<') && trimmed.ends_with('>') {
+                let tag = trimmed[1..trimmed.len() - 1].to_string();
+                match tag.as_str() {
+                    "TITLE" | "AUTHOR" | "COPYRIGHT" | "SIZE" | "GRID" | "REBUS" | "ACROSS" | "DOWN" | "NOTEPAD" => {}
+                    _ => return Err(TextError::new(number, format!("unknown section {}", trimmed))),
+                }
+                if sections.contains_key(&tag) {
+                    return Err(TextError::new(number, format!("duplicate section {}", trimmed)));
+                }
+                sections.insert(tag.clone(), Section { line: number, lines: vec![] });
+                current = Some(tag);
+            } else if let Some(tag) = &current {
+                let content = if tag == "NOTEPAD" { line.trim_end().trim_start_matches('\t') } else { trimmed };
+                sections.get_mut(tag).unwrap().lines.push((number, content.to_string()));
+            } else if !trimmed.is_empty() {
+                return Err(TextError::new(number, "text outside of a section"));
+            }
+        }
+        if !header {
+            return Err(TextError::new(1, "expected <ACROSS PUZZLE>"));
+        }
+        let single = |tag: &str| sections.get(tag).map_or(String::new(), |section| section.single());
+
+        let size_section = sections.get("SIZE").ok_or(TextError::new(1, "missing <SIZE>"))?;
+        let size_line = size_section.content().next().map_or(size_section.line, |(line, _)| *line);
+        let size_string = size_section.single();
+        let size = {
+            let mut parts = size_string.split('x').map(|part| part.trim().parse::<usize>().ok());
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(Some(width)), Some(Some(height)), None) if width > 0 && height > 0 => (width, height),
+                _ => return Err(TextError::new(size_line, format!("invalid size {:?}", size_string))),
+            }
+        };
+
+        let mut marked = false;
+        let mut rebuses = HashMap::new();
+        if let Some(section) = sections.get("REBUS") {
+            for (line, content) in section.content() {
+                if content == "MARK;" {
+                    marked = true;
+                    continue;
+                }
+                let parts: Vec<&str> = content.split(':').collect();
+                if parts.len() != 3 || parts[0].chars().count() != 1 || parts[1].is_empty() {
+                    return Err(TextError::new(*line, format!("invalid rebus {:?}", content)));
+                }
+                rebuses.insert(parts[0].chars().next().unwrap(), parts[1].to_string());
+            }
+        }
+
+        let grid_section = sections.get("GRID").ok_or(TextError::new(1, "missing <GRID>"))?;
+        let rows: Vec<&(usize, String)> = grid_section.content().collect();
+        if rows.len() != size.1 {
+            return Err(TextError::new(grid_section.line, format!("expected {} rows, found {}", size.1, rows.len())));
+        }
+        let mut cells = vec![];
+        for (line, row) in rows {
+            let row: Vec<char> = row.chars().collect();
+            if row.len() != size.0 {
+                return Err(TextError::new(*line, format!("expected {} columns, found {}", size.0, row.len())));
+            }
+            for c in row {
+                cells.push(match c {
+                    '.' => None,
+                    '-' => Some(PuzzleCell::default()),
+                    c if rebuses.contains_key(&c) => Some(PuzzleCell {
+                        solution: rebuses[&c].clone(),
+                        ..PuzzleCell::default()
+                    }),
+                    c if c.is_ascii_alphabetic() => Some(PuzzleCell {
+                        solution: c.to_ascii_uppercase().to_string(),
+                        circled: marked && c.is_ascii_lowercase(),
+                        ..PuzzleCell::default()
+                    }),
+                    c => return Err(TextError::new(*line, format!("unexpected {:?} in grid", c))),
+                });
+            }
+        }
+        let grid = Grid::new(size, |x, y| cells[x + y * size.0].clone());
+
+        let windows = WindowMap::from_grid(&Grid::new(size, |x, y| grid[(x, y)].is_some()));
+        let mut clues = WindowMap::new(windows.windows().map(|window| (window, String::new())), size);
+        for &(direction, tag) in &[(Direction::Across, "ACROSS"), (Direction::Down, "DOWN")] {
+            if let Some(section) = sections.get(tag) {
+                let targets: Vec<_> = windows.windows().filter(|window| window.direction() == direction).collect();
+                let lines: Vec<_> = section.content().collect();
+                if lines.len() != targets.len() {
+                    let line = lines.get(targets.len()).map_or(section.line, |(line, _)| *line);
+                    return Err(TextError::new(line, format!("expected {} {} clues, found {}", targets.len(), tag, lines.len())));
+                }
+                for (window, (_, clue)) in targets.into_iter().zip(lines) {
+                    clues[window] = clue.clone();
+                }
+            }
+        }
+
+        Ok(Puzzle {
+            preamble: vec![],
+            version: *b"1.4\0",
+            title: single("TITLE"),
+            author: single("AUTHOR"),
+            copyright: single("COPYRIGHT"),
+            grid,
+            clues,
+            note: sections.get("NOTEPAD").map_or(String::new(), |section| {
+                section.lines.iter().map(|(_, line)| line.as_str()).collect::<Vec<_>>().join("\n").trim().to_string()
+            }),
+            scrambled_checksum: None,
+            unknown_sections: vec![],
+            section_order: vec![],
+            play_data: None,
+        })
+    }
+
+    pub fn write_text(&self, write: &mut dyn Write) -> io::Result<()> {
+        let rebuses: BTreeSet<&str> = self.grid.iter()
+            .filter_map(|cell| cell.as_ref())
+            .map(|cell| cell.solution.as_str())
+            .filter(|solution| solution.chars().count() > 1)
+            .collect();
+        if rebuses.len() > REBUS_MARKERS.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "too many distinct rebus squares"));
+        }
+        let markers: HashMap<&str, char> = rebuses.iter().cloned().zip(REBUS_MARKERS.chars()).collect();
+        let marked = self.grid.iter().any(|cell| cell.as_ref().map_or(false, |cell| cell.circled));
+
+        writeln!(write, "<ACROSS PUZZLE V2>")?;
+        writeln!(write, "<TITLE>\n\t{}", self.title)?;
+        writeln!(write, "<AUTHOR>\n\t{}", self.author)?;
+        writeln!(write, "<COPYRIGHT>\n\t{}", self.copyright)?;
+        writeln!(write, "<SIZE>\n\t{}x{}", self.grid.size().0, self.grid.size().1)?;
+        writeln!(write, "<GRID>")?;
+        for y in 0..self.grid.size().1 {
+            let row: String = (0..self.grid.size().0).map(|x| match &self.grid[(x, y)] {
+                None => '.',
+                Some(cell) => match markers.get(cell.solution.as_str()) {
+                    Some(&marker) => marker,
+                    None => match cell.solution.chars().next() {
+                        None => '-',
+                        Some(c) if cell.circled => c.to_ascii_lowercase(),
+                        Some(c) => c,
+                    },
+                },
+            }).collect();
+            writeln!(write, "\t{}", row)?;
+        }
+        if marked || !rebuses.is_empty() {
+            writeln!(write, "<REBUS>")?;
+            if marked {
+                writeln!(write, "\tMARK;")?;
+            }
+            for (rebus, marker) in rebuses.iter().zip(REBUS_MARKERS.chars()) {
+                writeln!(write, "\t{}:{}:{}", marker, rebus, rebus.chars().next().unwrap())?;
+            }
+        }
+        for &(direction, tag) in &[(Direction::Across, "ACROSS"), (Direction::Down, "DOWN")] {
+            writeln!(write, "<{}>", tag)?;
+            for (_, clue) in self.clues.iter().filter(|(window, _)| window.direction() == direction) {
+                writeln!(write, "\t{}", clue)?;
+            }
+        }
+        if !self.note.is_empty() {
+            writeln!(write, "<NOTEPAD>")?;
+            for line in self.note.lines() {
+                writeln!(write, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_round_trip_read_write() {
+    let mut puzzle = super::puzzle::test_puzzle();
+    for cell in puzzle.grid.iter_mut() {
+        if let Some(cell) = cell {
+            cell.answer = String::new();
+        }
+    }
+    puzzle.note = "First line\nSecond line".to_string();
+    let mut data = vec![];
+    puzzle.write_text(&mut data).unwrap();
+    let puzzle2 = Puzzle::read_text(&mut data.as_slice()).unwrap();
+    assert_eq!(puzzle, puzzle2);
+}
+
+#[test]
+fn test_errors() {
+    fn error(text: &str) -> (usize, String) {
+        let error = Puzzle::read_text(&mut text.as_bytes()).unwrap_err();
+        (error.line, error.message)
+    }
+    assert_eq!(error("<TITLE>\n"), (1, "expected <ACROSS PUZZLE>".to_string()));
+    assert_eq!(error("<ACROSS PUZZLE>\n<SIZE>\n\t2x2\n<GRID>\n\tAB\n\tC\n").0, 6);
+    assert_eq!(error("<ACROSS PUZZLE>\n<SIZE>\n\t2x2\n<GRID>\n\tAB\n\tC!\n").0, 6);
+    assert_eq!(error("<ACROSS PUZZLE>\n<SIZE>\n\t2by2\n").0, 3);
+    assert_eq!(error("<ACROSS PUZZLE>\n<SIZE>\n\t2x2\n<GRID>\n\tAB\n\tCD\n<ACROSS>\n\tOne\n\tTwo\n\tThree\n").0, 10);
+    assert_eq!(error("<ACROSS PUZZLE>\n<CLUES>\n").0, 2);
+}
@@ -23,6 +23,9 @@ impl<T> Grid<T> {
     pub fn iter(&self) -> impl Iterator<Item=&T> {
         self.elements.iter()
     }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=&mut T> {
+        self.elements.iter_mut()
+    }
 }
 
 impl<T> Index<(usize, usize)> for Grid<T> {
@@ -7,3 +7,4 @@ pub mod tree_seq;
 pub mod bag;
 pub mod lines;
 pub mod product;
+pub mod range_split;
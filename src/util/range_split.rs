@@ -32,7 +32,7 @@ impl<T: Step + Clone, F: FnMut(&T) -> bool> Iterator for RangeSplit<T, F> {
                 match remainder.position(|x| (self.pred)(&x)) {
                     None => Some(range),
                     Some(position) => {
-                        let end = range.start.add_usize(position).unwrap();
+                        let end = Step::forward_checked(range.start.clone(), position).unwrap();
                         let result = range.start..end;
                         self.range = Some(remainder);
                         return Some(result);
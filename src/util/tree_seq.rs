@@ -17,8 +17,8 @@ impl<T> TreeSeq<T> {
     }
     pub fn concat(self, other: Self) -> Self {
         TreeSeq::Node {
-            left: box self,
-            right: box other,
+            left: Box::new(self),
+            right: Box::new(other),
         }
     }
     pub fn iter(&self) -> Iter<T> {